@@ -0,0 +1,64 @@
+use std::collections::{HashMap, VecDeque};
+
+use rust_physics_engine::common::config::ResolveMode;
+use rust_physics_engine::scalar::{vector2, Scalar};
+use rust_physics_engine::version_2d::world::World2D;
+
+const STEPS: usize = 3000;
+const DT: Scalar = 1.0 / 60.0;
+
+/// Grows a cluster from a fixed seed in the middle of a box of wandering balls. Every ball that
+/// touches the cluster sticks to it, building up a branching, fractal-like shape
+fn main() {
+    let mut world = World2D::builder()
+        .ball_count(600)
+        .ball_radius(5.0)
+        .size(800.0, 800.0)
+        .gravity(0.0)
+        .resistance(1.0)
+        .resolve_mode(ResolveMode::Sticky)
+        // A sparse gas gives most balls a cell to themselves, which is fine here
+        .configure(|config| config.cell_ratio_threshold = 1.0)
+        .build();
+
+    let center = vector2(400.0, 400.0);
+    world.remove_balls_near(center, 20.0);
+    let seed = world.add_kinematic_ball(center, vector2(0.0, 0.0), 5.0);
+
+    for _ in 0..STEPS {
+        world.step(DT);
+    }
+
+    // Everything bonded to the seed, directly or through other balls
+    let mut neighbours: HashMap<usize, Vec<usize>> = HashMap::new();
+    for bond in world.bonds() {
+        neighbours.entry(bond.id).or_default().push(bond.other_id);
+        neighbours.entry(bond.other_id).or_default().push(bond.id);
+    }
+
+    let mut cluster = vec![seed];
+    let mut visited = vec![false; world.balls.len()];
+    visited[seed] = true;
+    let mut queue = VecDeque::from([seed]);
+    while let Some(id) = queue.pop_front() {
+        for &other_id in neighbours.get(&id).into_iter().flatten() {
+            if !visited[other_id] {
+                visited[other_id] = true;
+                cluster.push(other_id);
+                queue.push_back(other_id);
+            }
+        }
+    }
+
+    let radius_of_gyration = (cluster
+        .iter()
+        .map(|&id| world.balls[id].position.distance_squared(center))
+        .sum::<Scalar>()
+        / cluster.len() as Scalar)
+        .sqrt();
+
+    println!("steps: {}", STEPS);
+    println!("bonds: {}", world.bonds().count());
+    println!("balls in the seed's cluster: {}", cluster.len());
+    println!("cluster radius of gyration: {:.2}", radius_of_gyration);
+}
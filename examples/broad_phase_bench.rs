@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use rust_physics_engine::quadtree::QuadTree;
+use rust_physics_engine::rng::Rng;
+use rust_physics_engine::scalar::{vector2, Scalar, Vector2};
+use rust_physics_engine::spatial_hash::SpatialHash;
+
+const BALLS: usize = 2000;
+const RADIUS: Scalar = 10.0;
+const SIZE: Scalar = 1200.0;
+const ROUNDS: usize = 50;
+
+fn main() {
+    let mut rng = Rng::new(42);
+
+    // Nine in ten balls crowd into one corner, the rest are spread over the whole area
+    let positions: Vec<(usize, Vector2)> = (0..BALLS)
+        .map(|id| {
+            let extent = if id % 10 == 0 { SIZE } else { SIZE / 3.0 };
+            (
+                id,
+                vector2(rng.gen_range(0.0, extent), rng.gen_range(0.0, extent)),
+            )
+        })
+        .collect();
+
+    let cell_size = RADIUS * 2.0 + 2.0;
+
+    let start = Instant::now();
+    let mut hash = SpatialHash::new(cell_size);
+    let mut hash_pairs = 0;
+    for _ in 0..ROUNDS {
+        hash.clear();
+        hash.update_all(&positions);
+        hash_pairs = hash.candidate_pairs().count();
+    }
+    let hash_time = start.elapsed() / ROUNDS as u32;
+
+    let start = Instant::now();
+    let mut tree = QuadTree::new(vector2(0.0, 0.0), vector2(SIZE, SIZE), cell_size, 8, 10);
+    let mut tree_pairs = 0;
+    for _ in 0..ROUNDS {
+        tree.update_all(&positions);
+        tree_pairs = tree.candidate_pairs().count();
+    }
+    let tree_time = start.elapsed() / ROUNDS as u32;
+
+    println!("balls: {}", BALLS);
+    println!(
+        "spatial hash: {:?} per rebuild, {} candidate pairs, {} cells",
+        hash_time,
+        hash_pairs,
+        hash.occupied_cells()
+    );
+    println!(
+        "quadtree: {:?} per rebuild, {} candidate pairs, {} leaves",
+        tree_time,
+        tree_pairs,
+        tree.leaf_count()
+    );
+}
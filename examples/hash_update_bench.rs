@@ -0,0 +1,69 @@
+use std::time::Instant;
+
+use rust_physics_engine::rng::Rng;
+use rust_physics_engine::scalar::{vector2, Scalar, Vector2};
+use rust_physics_engine::spatial_hash::SpatialHash;
+
+const BALLS: usize = 20000;
+const RADIUS: Scalar = 10.0;
+const SIZE: Scalar = 4000.0;
+const FRAMES: usize = 200;
+// One ball in this many is still moving, the rest have settled
+const MOVER_EVERY: usize = 10;
+const MOVER_SPEED: Scalar = 5.0;
+
+fn main() {
+    let mut rng = Rng::new(42);
+
+    let mut positions: Vec<(usize, Vector2)> = (0..BALLS)
+        .map(|id| {
+            (
+                id,
+                vector2(rng.gen_range(0.0, SIZE), rng.gen_range(0.0, SIZE)),
+            )
+        })
+        .collect();
+
+    // Every frame's positions up front, so both runs see exactly the same motion
+    let frames: Vec<Vec<(usize, Vector2)>> = (0..FRAMES)
+        .map(|_| {
+            for (id, position) in positions.iter_mut() {
+                if *id % MOVER_EVERY == 0 {
+                    *position += vector2(
+                        rng.gen_range(-MOVER_SPEED, MOVER_SPEED),
+                        rng.gen_range(-MOVER_SPEED, MOVER_SPEED),
+                    );
+                }
+            }
+            positions.clone()
+        })
+        .collect();
+
+    let cell_size = RADIUS * 2.0 + 2.0;
+
+    let mut hash = SpatialHash::new(cell_size);
+    hash.update_all(&frames[0]);
+    let start = Instant::now();
+    for frame in &frames {
+        hash.clear();
+        hash.update_all(frame);
+    }
+    let rebuild_time = start.elapsed() / FRAMES as u32;
+
+    let mut hash = SpatialHash::new(cell_size);
+    hash.update_all(&frames[0]);
+    let mut moved = 0;
+    let start = Instant::now();
+    for frame in &frames {
+        moved += hash.update_all(frame);
+    }
+    let update_time = start.elapsed() / FRAMES as u32;
+
+    println!("balls: {}, 1 in {} moving", BALLS, MOVER_EVERY);
+    println!("full rebuild: {:?} per frame", rebuild_time);
+    println!(
+        "update_all: {:?} per frame, {} balls changed cell per frame",
+        update_time,
+        moved / FRAMES
+    );
+}
@@ -0,0 +1,37 @@
+use rust_physics_engine::common::config::load_config_or_default;
+use rust_physics_engine::scalar::Scalar;
+use rust_physics_engine::version_2d::svg::export_svg;
+use rust_physics_engine::version_2d::world::World2D;
+
+const STEPS: usize = 600;
+const DT: Scalar = 1.0 / 60.0;
+
+fn main() {
+    let config = load_config_or_default("config.toml");
+
+    let args: Vec<String> = std::env::args().collect();
+    let svg_path = args
+        .iter()
+        .position(|arg| arg == "--export-svg")
+        .and_then(|index| args.get(index + 1));
+
+    let mut world = World2D::new(&config);
+
+    for _ in 0..STEPS {
+        world.step(DT);
+    }
+
+    println!("steps: {}", STEPS);
+    println!("balls: {}", world.balls.len());
+    println!("total kinetic energy: {:.2}", world.kinetic_energy());
+    println!("max overlap: {:.4}", world.max_overlap());
+    if world.track_penetration {
+        println!("peak penetration: {:.4}", world.peak_penetration());
+    }
+    println!("state hash: {:016x}", world.state_hash());
+
+    if let Some(path) = svg_path {
+        export_svg(&world, path, |ball| ball.color, None).expect("Failed to write SVG file");
+        println!("saved {}", path);
+    }
+}
@@ -0,0 +1,73 @@
+pub const MIN_SIM_STEPS: i32 = 1;
+pub const MAX_SIM_STEPS: i32 = 200;
+
+// Seconds at the floor, still short of the target frame rate, before warning about it
+const PINNED_WARNING_DELAY: f32 = 5.0;
+// Seconds between repeated warnings while it stays pinned
+const WARNING_INTERVAL: f32 = 60.0;
+
+/// Drives `sim_steps` from the frame rate for `auto_sim_steps`, one step down whenever a frame
+/// falls short of `target_fps` and one up whenever it beats it by more than `fps_boundary`
+///
+/// On a machine too slow for the scene the steps sink to the floor, where collisions are barely
+/// resolved. Rather than leave that to show up as mush, a warning is printed once it has been
+/// stuck there a while, and again every minute it stays stuck
+#[derive(Debug, Clone, Copy)]
+pub struct AutoSimSteps {
+    pub target_fps: i32,
+    pub fps_boundary: i32,
+    // Seconds spent at the floor while below the target frame rate
+    pinned_for: f32,
+    // Seconds until another warning may be printed
+    warning_cooldown: f32,
+    warnings: usize,
+}
+
+impl AutoSimSteps {
+    pub fn new(target_fps: i32, fps_boundary: i32) -> Self {
+        Self {
+            target_fps,
+            fps_boundary,
+            pinned_for: 0.0,
+            warning_cooldown: 0.0,
+            warnings: 0,
+        }
+    }
+
+    /// Takes this frame's rate and returns the sim steps to run next frame
+    pub fn update(&mut self, sim_steps: i32, fps: i32, dt: f32) -> i32 {
+        let sim_steps = if fps < self.target_fps {
+            sim_steps - 1
+        } else if fps > self.target_fps + self.fps_boundary {
+            sim_steps + 1
+        } else {
+            sim_steps
+        }
+        .clamp(MIN_SIM_STEPS, MAX_SIM_STEPS);
+
+        if sim_steps == MIN_SIM_STEPS && fps < self.target_fps {
+            self.pinned_for += dt;
+        } else {
+            self.pinned_for = 0.0;
+        }
+        self.warning_cooldown = (self.warning_cooldown - dt).max(0.0);
+
+        if self.pinned_for >= PINNED_WARNING_DELAY && self.warning_cooldown == 0.0 {
+            eprintln!(
+                "sim_steps has been stuck at {} for {:.0}s at {} FPS, short of target_fps {}. \
+                 Collisions will be poorly resolved: use fewer balls, or set auto_sim_steps = \
+                 false and pick sim_steps by hand",
+                MIN_SIM_STEPS, self.pinned_for, fps, self.target_fps
+            );
+            self.warning_cooldown = WARNING_INTERVAL;
+            self.warnings += 1;
+        }
+
+        sim_steps
+    }
+
+    /// Number of times the pinned warning has been printed
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
@@ -0,0 +1,23 @@
+/// The top of a colour scale, following the largest value shown but easing back down
+///
+/// Jumping straight to each frame's largest value makes the colours flicker whenever the one
+/// fastest ball changes, so the scale rises at once but only decays over time
+#[derive(Debug, Clone, Copy)]
+pub struct ColorScale {
+    pub max: f32,
+    /// Fraction of `max` kept after a second without anything reaching it
+    pub decay: f32,
+}
+
+impl ColorScale {
+    pub fn new(decay: f32) -> Self {
+        Self { max: 0.0, decay }
+    }
+
+    /// Takes this frame's largest value and returns the scale to colour it with
+    pub fn update(&mut self, current_max: f32, dt: f32) -> f32 {
+        // Raised to the frame time so the scale decays at the same rate whatever the frame rate
+        self.max = current_max.max(self.max * self.decay.powf(dt));
+        self.max
+    }
+}
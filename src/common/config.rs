@@ -1,28 +1,374 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::ErrorKind;
 
-#[derive(Debug, Deserialize)]
+use toml::{Table, Value};
+
+// Compiled in so a fresh checkout runs without any setup
+const DEFAULT_CONFIG: &str = include_str!("../../config.default.toml");
+
+/// How the 2D world places its balls at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum InitLayout {
+    Random,
+    Grid,
+    HexPack,
+    /// Random, but with no two balls closer than `layout_gap`
+    Spaced,
+}
+
+/// How balls are kept inside the world bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BoundaryMode {
+    /// Snap back to the wall and reflect the velocity
+    Hard,
+    /// Push back with a damped spring proportional to the penetration depth
+    Soft,
+}
+
+/// How overlapping balls are pushed apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ResolveMode {
+    /// Separate the pair and exchange a bouncing impulse
+    Impulse,
+    /// Only separate the pair, leaving velocities untouched, like a packing relaxation
+    PositionOnly,
+    /// Stop the pair approaching without a bounce, then bond it for good at the distance they
+    /// touch at, so touching balls grow into clusters that move as one
+    Sticky,
+}
+
+/// How the strength of gravity changes across the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum GravityFalloff {
+    /// The same downward pull everywhere
+    Constant,
+    /// Full strength at the floor, fading linearly to nothing at the ceiling, like a thin atmosphere
+    Linear,
+    /// Pulls towards `gravity_center`, at full strength `gravity_radius` away and falling off with
+    /// the square of the distance beyond that
+    InverseSquare,
+}
+
+/// How two materials respond to touching, see `material_pairs`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MaterialPair {
+    /// The two `material_id`s, in either order
+    pub materials: [usize; 2],
+    /// The pair's restitution, in place of the world's `bounce_amount`
+    pub bounce_amount: f32,
+    /// The pair's friction, in place of the world's `contact_damping`
+    pub contact_damping: f32,
+}
+
+/// Behaviour of one side of the world bounds
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Wall {
+    /// Fraction of a ball's speed into the wall that it keeps after bouncing
+    pub restitution: f32,
+    /// Lets balls pass in from outside the bounds while still stopping them from leaving
+    pub one_way: bool,
+}
+
+impl Wall {
+    /// Whether a ball is stopped by this wall, given whether it is heading out through it or
+    /// sliding along it rather than coming in
+    pub fn blocks(&self, heading_out: bool) -> bool {
+        !self.one_way || heading_out
+    }
+}
+
+/// Current config format. Version 2 made `gravity` an acceleration in units/s² instead of a
+/// velocity added once per frame
+pub const CONFIG_VERSION: u32 = 2;
+
+// Frame rate the old per-frame gravity values were tuned at
+const LEGACY_FRAME_RATE: f32 = 60.0;
+
+// Files written before versioning have no `config_version` key
+fn legacy_config_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    #[serde(default = "legacy_config_version")]
+    pub config_version: u32,
+    /// Profile from `[profiles.<name>]` to start with, the top-level keys alone if unset
+    pub active_profile: Option<String>,
     pub ball_count_2d: usize,
+    /// Fraction of the 2D world's area to cover with balls, replacing `ball_count_2d` if set
+    pub fill_fraction: Option<f32>,
     pub ball_count_3d: usize,
+    /// Seed for the 2D world's random layout, velocities and colours
+    pub seed: u64,
     pub ball_radius: f32,
     pub gravity: f32,
+    pub attraction_strength: f32,
+    pub gravity_falloff: GravityFalloff,
+    pub gravity_center: [f32; 2],
+    pub gravity_radius: f32,
+    /// Swing of gravity about `gravity` in units/s², following a sine wave to shake the world
+    pub gravity_pulse_amplitude: f32,
+    /// Cycles per second of simulated time of the gravity pulse
+    pub gravity_pulse_frequency: f32,
+    /// Gravity multiplier for new 2D balls, negative to make them float
+    pub gravity_scale: f32,
+    /// Randomises each new 2D ball's gravity multiplier by up to this much either way
+    pub gravity_scale_spread: f32,
     pub resistance: f32,
     pub bounce_amount: f32,
+    /// Contacts approaching slower than this don't bounce at all, so resting balls settle
+    /// instead of micro-bouncing. 0 bounces at every speed
+    pub restitution_velocity_threshold: f32,
     pub max_speed: f32,
     pub max_pressure: f32,
+    pub max_correction: f32,
+    /// Cap each contact's impulse at what would reverse the pair's approach, so overlaps can't
+    /// add energy
+    pub clamp_contact_impulse: bool,
+    pub contact_damping: f32,
+    pub resolve_mode: ResolveMode,
+    /// Size of the world the balls are kept inside, which is also the window's starting size
     pub width: f32,
     pub height: f32,
     pub depth: f32,
     pub sim_steps: i32,
     pub auto_sim_steps: bool,
+    /// Ends a step's solver iterations early once no contact moved further than this in one,
+    /// 0 to always run all `sim_steps`
+    pub convergence_eps: f32,
+    /// Record the deepest overlap between touching balls over the whole run and print it on
+    /// exit, to find the fewest `sim_steps` that keep it within budget
+    pub track_penetration: bool,
     pub target_fps: i32,
+    /// Sleeps away the rest of each frame to stay at or below this rate, `None` for no cap
+    pub max_fps: Option<u32>,
     pub fps_boundary: i32,
     pub delete_dist: f32,
+    pub background_color: [f32; 3],
+    pub auto_pressure_scale: bool,
+    pub pressure_display_max: f32,
+    /// Fraction of the speed, pressure and acceleration colour scales kept after a second in
+    /// which no ball reaches them, 0 to rescale to the current largest value every frame
+    pub color_scale_decay: f32,
+    /// Fade distant balls towards the background in the 3D version
+    pub depth_fog: bool,
+    pub init_layout: InitLayout,
+    pub layout_gap: f32,
+    pub boundary_mode: BoundaryMode,
+    pub wall_stiffness: f32,
+    pub wall_damping: f32,
+    pub limit_displacement: bool,
+    pub cohesion_strength: f32,
+    pub interaction_radius: f32,
+    pub island_solver: bool,
+    pub parallel_solver: bool,
+    /// Resolve the contacts in a fresh seeded random order each solver iteration
+    pub randomize_contact_order: bool,
+    /// Most contacts resolved per ball each step, deepest first, all of them if unset
+    pub max_contacts_per_body: Option<usize>,
+    pub min_event_impulse: f32,
+    /// Seconds a ball is left out of collision events after being in one
+    pub event_cooldown: f32,
+    pub adaptive_substeps: bool,
+    pub cell_ratio_threshold: f32,
+    pub auto_cell_size: bool,
+    pub substep_fraction: f32,
+    pub max_substeps: usize,
+    pub max_balls: usize,
+    pub emitter_rate: f32,
+    /// Full width of the emitter cone, in degrees
+    pub emitter_spread_angle: f32,
+    pub emitter_speed: f32,
+    pub emitter_lifetime: f32,
+    /// Seconds balls spawned with the mouse live for, forever if unset
+    pub spawn_lifetime: Option<f32>,
+    /// Fraction of the cursor's velocity that balls spawned with the mouse are thrown with
+    pub spawn_throw: f32,
+    /// Most that random spread adds to a spawned ball's velocity on each axis, in units/s
+    pub spawn_velocity_spread: f32,
+    /// Fraction of a ball's lifetime spent fading out before it is removed
+    pub fade_fraction: f32,
+    /// Total kinetic energy the scene must stay below for `settle_frames` frames to count as
+    /// settled
+    pub settle_energy_threshold: f32,
+    pub settle_frames: usize,
+    /// Frames of the 2D version kept to step back through while paused, 0 to keep none
+    pub rewind_frames: usize,
+    /// Pull of black holes placed with the mouse at their horizon, in units/s²
+    pub black_hole_strength: f32,
+    pub black_hole_horizon: f32,
+    pub black_hole_accretes: bool,
+    /// Size of the drains placed in the 2D version
+    pub drain_width: f32,
+    pub drain_height: f32,
+    /// Steps the 2D world in fixed `fixed_dt` increments and interpolates drawing between them
+    pub fixed_timestep: bool,
+    pub fixed_dt: f32,
+    /// Cap on fixed steps per frame so a slow frame can't snowball into ever more steps
+    pub max_steps_per_tick: usize,
+    /// Bounce and damping for specific pairs of 2D `Ball::material_id`s, any pair not listed
+    /// uses `bounce_amount` and `contact_damping`
+    pub material_pairs: Vec<MaterialPair>,
+    pub walls_2d: [Wall; 4], // Left, right, top, bottom
+    pub walls_3d: [Wall; 6], // Left, right, top, bottom, front, back
+}
+
+impl Default for Config {
+    /// The top-level keys of the built-in `config.default.toml`
+    fn default() -> Self {
+        parse_config(DEFAULT_CONFIG)
+    }
+}
+
+/// Name of the profile made of just the top-level keys
+pub const BASE_PROFILE: &str = "default";
+
+/// Top-level keys of the built-in defaults, for whatever an older or trimmed down file leaves out
+fn default_keys() -> Table {
+    let mut defaults: Table =
+        toml::from_str(DEFAULT_CONFIG).expect("Failed to parse default configuration");
+    defaults.remove("profiles");
+    defaults
+}
+
+/// The `config_version` a table of keys was written for, `fallback` if it doesn't say
+fn table_version(table: &Table, fallback: u32) -> u32 {
+    table
+        .get("config_version")
+        .and_then(Value::as_integer)
+        .map_or(fallback, |version| version as u32)
+}
+
+/// Upgrades keys from an older config format so they behave as they used to
+///
+/// Only the keys in `table` are touched, so it must run before anything is inherited from the
+/// defaults or the top-level keys, which are already in the current format
+fn migrate(table: &mut Table, version: u32) {
+    if version < 2 {
+        let scaled = match table.get("gravity") {
+            Some(Value::Float(gravity)) => Some(*gravity * LEGACY_FRAME_RATE as f64),
+            Some(Value::Integer(gravity)) => Some(*gravity as f64 * LEGACY_FRAME_RATE as f64),
+            _ => None,
+        };
+
+        if let Some(gravity) = scaled {
+            table.insert("gravity".to_string(), Value::Float(gravity));
+            eprintln!(
+                "config_version {} is outdated, scaling gravity to {} units/s²",
+                version, gravity
+            );
+        }
+    }
+
+    table.insert(
+        "config_version".to_string(),
+        Value::Integer(CONFIG_VERSION as i64),
+    );
+}
+
+fn parse_profiles(config_content: &str) -> HashMap<String, Config> {
+    let mut file: Table =
+        toml::from_str(config_content).expect("Failed to parse configuration file");
+
+    let overrides = match file.remove("profiles") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => panic!("Failed to parse configuration file: profiles must be a table"),
+        None => Table::new(),
+    };
+
+    let file_version = table_version(&file, legacy_config_version());
+    migrate(&mut file, file_version);
+
+    let mut base = default_keys();
+    base.extend(file);
+
+    let mut profiles = HashMap::new();
+
+    for (name, profile) in overrides {
+        let Value::Table(mut profile) = profile else {
+            panic!(
+                "Failed to parse configuration file: profile {} must be a table",
+                name
+            );
+        };
+
+        // A profile without its own version was written alongside the top-level keys
+        let profile_version = table_version(&profile, file_version);
+        migrate(&mut profile, profile_version);
+
+        // Profiles only list what they change, everything else comes from the top-level keys
+        let mut merged = base.clone();
+        merged.extend(profile);
+
+        let config = Config::deserialize(merged)
+            .unwrap_or_else(|error| panic!("Failed to parse profile {}: {}", name, error));
+        profiles.insert(name, config);
+    }
+
+    let config = Config::deserialize(base).expect("Failed to parse configuration file");
+    profiles.insert(BASE_PROFILE.to_string(), config);
+
+    profiles
+}
+
+fn parse_config(config_content: &str) -> Config {
+    let mut profiles = parse_profiles(config_content);
+
+    let active = profiles[BASE_PROFILE]
+        .active_profile
+        .clone()
+        .unwrap_or(BASE_PROFILE.to_string());
+
+    profiles.remove(&active).unwrap_or_else(|| {
+        eprintln!("Unknown active_profile {}, using {}", active, BASE_PROFILE);
+        profiles.remove(BASE_PROFILE).unwrap()
+    })
+}
+
+/// Loads every profile in the config at `path`, keyed by name, with the top-level keys under
+/// `BASE_PROFILE`
+pub fn load_profiles(path: &str) -> HashMap<String, Config> {
+    let config_content = fs::read_to_string(path).expect("Failed to read configuration file");
+
+    parse_profiles(&config_content)
 }
 
+/// Loads the active profile of the config at `path`
 pub fn load_config(path: &str) -> Config {
     let config_content = fs::read_to_string(path).expect("Failed to read configuration file");
 
-    toml::from_str(&config_content).expect("Failed to parse configuration file")
+    parse_config(&config_content)
+}
+
+/// Reads the config at `path`, falling back to the built-in defaults if the file doesn't exist
+#[cfg(not(target_arch = "wasm32"))]
+fn read_config_or_default(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            eprintln!("{} not found, using default configuration", path);
+            DEFAULT_CONFIG.to_string()
+        }
+        Err(error) => panic!("Failed to read configuration file: {}", error),
+    }
+}
+
+/// Browsers have no filesystem to read `path` from, so the built-in defaults are always used
+#[cfg(target_arch = "wasm32")]
+fn read_config_or_default(_path: &str) -> String {
+    DEFAULT_CONFIG.to_string()
+}
+
+/// Like `load_config`, falling back to the built-in defaults if the file doesn't exist
+pub fn load_config_or_default(path: &str) -> Config {
+    parse_config(&read_config_or_default(path))
+}
+
+/// Like `load_profiles`, falling back to the built-in defaults if the file doesn't exist
+pub fn load_profiles_or_default(path: &str) -> HashMap<String, Config> {
+    parse_profiles(&read_config_or_default(path))
 }
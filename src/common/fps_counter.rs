@@ -7,6 +7,12 @@ pub struct SmoothedFps {
     pub count: usize,
 }
 
+impl Default for SmoothedFps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SmoothedFps {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,47 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Caps the frame rate by sleeping through whatever is left of each frame's time budget
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    // When the last frame was due to end, so time spent oversleeping comes off the next frame
+    last_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        // The browser paces frames itself, and has no clock or sleep to limit them with
+        let frame_time = if cfg!(target_arch = "wasm32") {
+            None
+        } else {
+            max_fps
+                .filter(|&fps| fps > 0)
+                .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+        };
+
+        Self {
+            frame_time,
+            last_frame: None,
+        }
+    }
+
+    /// Sleeps until a full frame time has passed since the previous call, counting the time
+    /// the frame already took
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+
+        let now = Instant::now();
+        let deadline = self.last_frame.map(|last| last + frame_time);
+
+        self.last_frame = match deadline {
+            Some(deadline) if deadline > now => {
+                thread::sleep(deadline - now);
+                Some(deadline)
+            }
+            // Running behind, so start counting again from now rather than racing to catch up
+            _ => Some(now),
+        };
+    }
+}
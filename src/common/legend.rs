@@ -0,0 +1,40 @@
+use macroquad::prelude::*;
+
+const LEGEND_WIDTH: f32 = 200.0;
+const LEGEND_HEIGHT: f32 = 16.0;
+const LEGEND_STEPS: usize = 50;
+
+/// Draws a gradient bar in the top right corner mapping colours back to values from 0 to `max_value`
+pub fn draw_legend(label: &str, max_value: f32, gradient: fn(f32) -> Color) {
+    let x = screen_width() - LEGEND_WIDTH - 20.0;
+    let y = 40.0;
+    let step_width = LEGEND_WIDTH / LEGEND_STEPS as f32;
+
+    for step in 0..LEGEND_STEPS {
+        let t = step as f32 / (LEGEND_STEPS - 1) as f32;
+
+        draw_rectangle(
+            x + step as f32 * step_width,
+            y,
+            step_width,
+            LEGEND_HEIGHT,
+            gradient(t),
+        );
+    }
+
+    draw_rectangle_lines(x, y, LEGEND_WIDTH, LEGEND_HEIGHT, 1.0, WHITE);
+
+    draw_text(label, x, y - 8.0, 20.0, WHITE);
+    draw_text("0", x, y + LEGEND_HEIGHT + 18.0, 20.0, WHITE);
+
+    let max_label = format!("{:.2}", max_value);
+    let max_label_width = measure_text(&max_label, None, 20, 1.0).width;
+
+    draw_text(
+        &max_label,
+        x + LEGEND_WIDTH - max_label_width,
+        y + LEGEND_HEIGHT + 18.0,
+        20.0,
+        WHITE,
+    );
+}
@@ -1,4 +1,36 @@
+pub mod narrow_phase;
+pub mod quadtree;
+pub mod rng;
+pub mod scalar;
+pub mod shape;
+pub mod spatial_hash;
+pub mod spatial_hash_3d;
+
 pub mod common {
+    pub mod auto_sim_steps;
+    pub mod color_scale;
     pub mod config;
     pub mod fps_counter;
+    pub mod frame_limiter;
+    #[cfg(feature = "macroquad")]
+    pub mod legend;
+}
+
+#[cfg(feature = "macroquad")]
+pub mod version_2d {
+    pub mod ball_csv;
+    pub mod builder;
+    pub mod coloring;
+    pub mod island;
+    pub mod layout;
+    pub mod material;
+    pub mod metrics;
+    pub mod rewind;
+    pub mod svg;
+    pub mod world;
+}
+
+#[cfg(feature = "macroquad")]
+pub mod version_3d {
+    pub mod world;
 }
@@ -0,0 +1,240 @@
+//! Exact contact tests between any two convex `Shape`s through GJK and EPA
+//!
+//! GJK walks the Minkowski difference of the two shapes, built only from their support points,
+//! until it either encloses the origin (they overlap) or shows it can't. EPA then grows the
+//! enclosing triangle outwards to find the edge nearest the origin, which gives the
+//! penetration normal and depth
+
+use crate::scalar::{Scalar, Vector2};
+use crate::shape::{Shape, Transform};
+
+// Both loops stop early once they converge, these only guard against curved shapes never
+// quite getting there
+const MAX_GJK_ITERATIONS: usize = 32;
+const MAX_EPA_ITERATIONS: usize = 64;
+
+// Circles closer than this have no usable direction between them
+const COINCIDENT_DISTANCE: Scalar = 1e-6;
+
+// How close EPA's nearest edge has to get to the true boundary before it stops expanding
+const EPA_TOLERANCE: Scalar = 1e-4;
+
+/// How two overlapping shapes touch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Unit direction from the first shape towards the second, the way to push the second out
+    pub normal: Vector2,
+    /// How far the shapes overlap along `normal`
+    pub depth: Scalar,
+}
+
+/// The closed-form contact between two circles, `None` when they don't overlap
+///
+/// Balls use this instead of `collide`, which would only approximate the curved boundary.
+/// Circles on top of each other are pushed apart along +x, so the result never has NaNs
+pub fn collide_circles(
+    position: Vector2,
+    radius: Scalar,
+    other_position: Vector2,
+    other_radius: Scalar,
+) -> Option<Contact> {
+    let dist = position.distance(other_position);
+    let depth = (radius + other_radius) - dist;
+
+    if depth <= 0.0 {
+        return None;
+    }
+
+    let normal = if dist < COINCIDENT_DISTANCE {
+        Vector2::X
+    } else {
+        (other_position - position) / dist
+    };
+
+    Some(Contact { normal, depth })
+}
+
+/// Point of the Minkowski difference `a - b` furthest along `direction`
+fn support<A: Shape + ?Sized, B: Shape + ?Sized>(
+    a: &A,
+    a_transform: &Transform,
+    b: &B,
+    b_transform: &Transform,
+    direction: Vector2,
+) -> Vector2 {
+    let a_point = a_transform.apply(a.support(a_transform.unrotate(direction)));
+    let b_point = b_transform.apply(b.support(b_transform.unrotate(-direction)));
+    a_point - b_point
+}
+
+/// Whether the two shapes overlap. Shapes that only touch don't count
+pub fn intersects<A: Shape + ?Sized, B: Shape + ?Sized>(
+    a: &A,
+    a_transform: &Transform,
+    b: &B,
+    b_transform: &Transform,
+) -> bool {
+    collide(a, a_transform, b, b_transform).is_some()
+}
+
+/// Contact between two convex shapes, `None` if they don't overlap or only touch
+pub fn collide<A: Shape + ?Sized, B: Shape + ?Sized>(
+    a: &A,
+    a_transform: &Transform,
+    b: &B,
+    b_transform: &Transform,
+) -> Option<Contact> {
+    let simplex = gjk(a, a_transform, b, b_transform)?;
+    let contact = epa(simplex, |direction| {
+        support(a, a_transform, b, b_transform, direction)
+    });
+
+    // GJK can enclose the origin on the triangle's edge when the shapes only touch
+    (contact.depth > EPA_TOLERANCE).then_some(contact)
+}
+
+/// A triangle of the Minkowski difference enclosing the origin, if there is one
+fn gjk<A: Shape + ?Sized, B: Shape + ?Sized>(
+    a: &A,
+    a_transform: &Transform,
+    b: &B,
+    b_transform: &Transform,
+) -> Option<[Vector2; 3]> {
+    let support = |direction| support(a, a_transform, b, b_transform, direction);
+
+    let mut direction = b_transform.position - a_transform.position;
+    if direction == Vector2::ZERO {
+        direction = Vector2::X;
+    }
+
+    // Newest point last
+    let mut simplex = vec![support(direction)];
+    direction = -simplex[0];
+
+    for _ in 0..MAX_GJK_ITERATIONS {
+        // The origin is a support point, on the boundary, so the shapes only touch
+        if direction == Vector2::ZERO {
+            return None;
+        }
+
+        let point = support(direction);
+        if point.dot(direction) <= 0.0 {
+            return None;
+        }
+
+        simplex.push(point);
+        if let Some(triangle) = next_simplex(&mut simplex, &mut direction) {
+            return Some(triangle);
+        }
+    }
+
+    None
+}
+
+/// Cuts the simplex down to the part nearest the origin and points `direction` from it towards
+/// the origin, returning the triangle once it encloses the origin
+fn next_simplex(simplex: &mut Vec<Vector2>, direction: &mut Vector2) -> Option<[Vector2; 3]> {
+    match *simplex.as_slice() {
+        [b, a] => {
+            let ab = b - a;
+            let ao = -a;
+
+            if ab.dot(ao) > 0.0 {
+                // With the origin on the segment either side will do, and the triangle built
+                // from it still encloses the origin, if only on an edge
+                let perp = ab.perp();
+                *direction = if perp.dot(ao) < 0.0 { -perp } else { perp };
+            } else {
+                *simplex = vec![a];
+                *direction = ao;
+            }
+            None
+        }
+        [c, b, a] => {
+            let ab = b - a;
+            let ac = c - a;
+            let ao = -a;
+
+            // Each edge's normal facing away from the triangle's third point
+            let ab_perp = if ab.perp().dot(ac) > 0.0 {
+                -ab.perp()
+            } else {
+                ab.perp()
+            };
+            let ac_perp = if ac.perp().dot(ab) > 0.0 {
+                -ac.perp()
+            } else {
+                ac.perp()
+            };
+
+            if ab_perp.dot(ao) > 0.0 {
+                *simplex = vec![b, a];
+                *direction = ab_perp;
+                None
+            } else if ac_perp.dot(ao) > 0.0 {
+                *simplex = vec![c, a];
+                *direction = ac_perp;
+                None
+            } else {
+                Some([c, b, a])
+            }
+        }
+        _ => unreachable!("GJK simplex has {} points", simplex.len()),
+    }
+}
+
+/// Expands the triangle from GJK until its nearest edge to the origin lies on the boundary of
+/// the Minkowski difference
+fn epa(triangle: [Vector2; 3], support: impl Fn(Vector2) -> Vector2) -> Contact {
+    let mut polytope = triangle.to_vec();
+
+    // Anticlockwise, so every edge's outward normal is on its right
+    if (polytope[1] - polytope[0]).perp_dot(polytope[2] - polytope[0]) < 0.0 {
+        polytope.swap(1, 2);
+    }
+
+    let mut nearest = nearest_edge(&polytope);
+    for _ in 0..MAX_EPA_ITERATIONS {
+        let (index, contact) = nearest;
+        let point = support(contact.normal);
+
+        if point.dot(contact.normal) - contact.depth < EPA_TOLERANCE {
+            break;
+        }
+
+        polytope.insert(index + 1, point);
+        nearest = nearest_edge(&polytope);
+    }
+
+    nearest.1
+}
+
+/// The polytope edge nearest the origin, as the index of its first point and its outward
+/// normal and distance
+fn nearest_edge(polytope: &[Vector2]) -> (usize, Contact) {
+    let mut nearest = (
+        0,
+        Contact {
+            normal: Vector2::ZERO,
+            depth: Scalar::INFINITY,
+        },
+    );
+
+    for (index, &start) in polytope.iter().enumerate() {
+        let end = polytope[(index + 1) % polytope.len()];
+        let edge = end - start;
+        let normal = Vector2::new(edge.y, -edge.x).normalize_or_zero();
+
+        // Repeated support points leave zero-length edges with no direction
+        if normal == Vector2::ZERO {
+            continue;
+        }
+
+        let depth = normal.dot(start);
+        if depth < nearest.1.depth {
+            nearest = (index, Contact { normal, depth });
+        }
+    }
+
+    nearest
+}
@@ -0,0 +1,220 @@
+use crate::scalar::{Scalar, Vector2};
+
+// Index of a node's first child, the other three follow it in the node list
+type FirstChild = usize;
+
+#[derive(Debug)]
+struct Node {
+    min: Vector2,
+    max: Vector2,
+    depth: usize,
+    children: Option<FirstChild>,
+    objects: Vec<usize>, // Indices into the tree's object list, only used by leaves
+}
+
+impl Node {
+    fn new(min: Vector2, max: Vector2, depth: usize) -> Self {
+        Self {
+            min,
+            max,
+            depth,
+            children: None,
+            objects: Vec::new(),
+        }
+    }
+
+    fn overlaps(&self, min: Vector2, max: Vector2) -> bool {
+        self.min.x <= max.x && min.x <= self.max.x && self.min.y <= max.y && min.y <= self.max.y
+    }
+}
+
+/// Broad-phase that subdivides only where objects are dense, as an alternative to the uniform
+/// `SpatialHash` for scenes where most objects crowd into a small area
+///
+/// A leaf splits into four once it holds more than `max_objects`, unless it is already
+/// `max_depth` levels deep. Objects outside the bounds it was created with are still paired,
+/// they just aren't subdivided
+#[derive(Debug)]
+pub struct QuadTree<ID> {
+    reach: Scalar,
+    max_objects: usize,
+    max_depth: usize,
+    nodes: Vec<Node>,            // The root is always node 0
+    objects: Vec<(Vector2, ID)>, // Every stored object, in insertion order
+    outside: Vec<usize>,         // Objects outside the root's bounds
+}
+
+impl<ID: Clone> QuadTree<ID> {
+    /// Creates an empty tree covering `min` to `max`, pairing objects at most `reach` apart on
+    /// both axes
+    pub fn new(
+        min: Vector2,
+        max: Vector2,
+        reach: Scalar,
+        max_objects: usize,
+        max_depth: usize,
+    ) -> Self {
+        Self {
+            reach,
+            max_objects: max_objects.max(1),
+            max_depth,
+            nodes: vec![Node::new(min, max, 0)],
+            objects: Vec::new(),
+            outside: Vec::new(),
+        }
+    }
+
+    /// Inserts an object ID into the leaf holding its position, splitting the leaf if it is full
+    pub fn insert(&mut self, position: Vector2, id: ID) {
+        let index = self.objects.len();
+        self.objects.push((position, id));
+
+        let root = &self.nodes[0];
+        if position.x < root.min.x
+            || position.x > root.max.x
+            || position.y < root.min.y
+            || position.y > root.max.y
+        {
+            self.outside.push(index);
+            return;
+        }
+
+        self.insert_into(0, index);
+    }
+
+    fn insert_into(&mut self, node: usize, index: usize) {
+        let mut node = node;
+        while let Some(first_child) = self.nodes[node].children {
+            node = first_child + self.quadrant(node, self.objects[index].0);
+        }
+
+        self.nodes[node].objects.push(index);
+
+        if self.nodes[node].objects.len() > self.max_objects
+            && self.nodes[node].depth < self.max_depth
+        {
+            self.split(node);
+        }
+    }
+
+    /// Which of the node's four children a position falls into
+    fn quadrant(&self, node: usize, position: Vector2) -> usize {
+        let center = (self.nodes[node].min + self.nodes[node].max) * 0.5;
+        (position.x >= center.x) as usize + 2 * (position.y >= center.y) as usize
+    }
+
+    fn split(&mut self, node: usize) {
+        let Node {
+            min, max, depth, ..
+        } = self.nodes[node];
+        let center = (min + max) * 0.5;
+
+        let first_child = self.nodes.len();
+        for quadrant in 0..4 {
+            let (x_min, x_max) = if quadrant & 1 == 0 {
+                (min.x, center.x)
+            } else {
+                (center.x, max.x)
+            };
+            let (y_min, y_max) = if quadrant & 2 == 0 {
+                (min.y, center.y)
+            } else {
+                (center.y, max.y)
+            };
+
+            self.nodes.push(Node::new(
+                Vector2::new(x_min, y_min),
+                Vector2::new(x_max, y_max),
+                depth + 1,
+            ));
+        }
+        self.nodes[node].children = Some(first_child);
+
+        for index in std::mem::take(&mut self.nodes[node].objects) {
+            self.insert_into(node, index);
+        }
+    }
+
+    /// Empties the tree and inserts the batch again, since a tree is cheaper to rebuild than to
+    /// update object by object
+    pub fn update_all(&mut self, positions: &[(ID, Vector2)]) {
+        self.clear();
+
+        for (id, position) in positions {
+            self.insert(*position, id.clone());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0].children = None;
+        self.nodes[0].objects.clear();
+        self.objects.clear();
+        self.outside.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Number of leaves, which grows with how unevenly the objects are spread
+    pub fn leaf_count(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.children.is_none())
+            .count()
+    }
+
+    /// Indices of every object within `reach` of `position` on both axes
+    fn query(&self, position: Vector2, found: &mut Vec<usize>, stack: &mut Vec<usize>) {
+        let min = position - Vector2::splat(self.reach);
+        let max = position + Vector2::splat(self.reach);
+        let within_reach = |index: &usize| {
+            let other = self.objects[*index].0;
+            (other.x - position.x).abs() <= self.reach && (other.y - position.y).abs() <= self.reach
+        };
+
+        found.extend(self.outside.iter().filter(|index| within_reach(index)));
+
+        stack.clear();
+        stack.push(0);
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.overlaps(min, max) {
+                continue;
+            }
+
+            match node.children {
+                Some(first_child) => stack.extend(first_child..first_child + 4),
+                None => found.extend(node.objects.iter().filter(|index| within_reach(index))),
+            }
+        }
+    }
+
+    /// Yields every pair of objects within `reach` of each other on both axes exactly once
+    ///
+    /// Each object only pairs with the ones inserted after it, so the mirrored pair is never
+    /// produced
+    pub fn candidate_pairs(&self) -> impl Iterator<Item = (ID, ID)> + '_ {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+
+        self.objects
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, (position, id))| {
+                found.clear();
+                self.query(*position, &mut found, &mut stack);
+
+                found
+                    .iter()
+                    .filter(|&&other| other > index)
+                    .map(|&other| (id.clone(), self.objects[other].1.clone()))
+                    .collect::<Vec<_>>()
+            })
+    }
+}
@@ -0,0 +1,32 @@
+//! Floating-point precision of the 2D simulation
+//!
+//! `World2D` and the spatial hash do all their maths through these aliases, so switching the
+//! engine to double precision only means changing this file: `Scalar` to `f64`, `Vector2` to
+//! `glam::DVec2`, `vector2` to `glam::dvec2`, `consts` to `std::f64::consts` and the
+//! conversions to `as f32`, `as_dvec2` and `as_vec2`.
+//!
+//! f64 cuts the energy drift of very long runs, at roughly twice the memory per ball and half
+//! the SIMD throughput. The config file and rendering stay f32 and convert at the boundary.
+
+use glam::Vec2;
+
+pub type Scalar = f32;
+pub type Vector2 = Vec2;
+
+pub use glam::vec2 as vector2;
+pub use std::f32::consts;
+
+/// Converts a render or input position into the simulation's precision
+pub fn from_vec2(v: Vec2) -> Vector2 {
+    v
+}
+
+/// Converts a simulation value to f32 for rendering
+pub fn to_f32(x: Scalar) -> f32 {
+    x
+}
+
+/// Converts a simulation vector to f32 for rendering
+pub fn to_vec2(v: Vector2) -> Vec2 {
+    v
+}
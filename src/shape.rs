@@ -0,0 +1,162 @@
+//! Body shapes, described through one trait so the broad and narrow phases can treat them alike
+
+use crate::scalar::{consts, Scalar, Vector2};
+
+/// Where a shape is placed: its centre and how far it is turned anticlockwise, in radians
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Vector2,
+    pub rotation: Scalar,
+}
+
+impl Transform {
+    /// An unrotated transform centred on `position`
+    pub fn at(position: Vector2) -> Self {
+        Self {
+            position,
+            rotation: 0.0,
+        }
+    }
+
+    /// Moves a point from the shape's local space into world space
+    pub fn apply(&self, point: Vector2) -> Vector2 {
+        self.position + Vector2::from_angle(self.rotation).rotate(point)
+    }
+
+    /// Turns a world space direction into the shape's local space
+    pub fn unrotate(&self, direction: Vector2) -> Vector2 {
+        Vector2::from_angle(-self.rotation).rotate(direction)
+    }
+}
+
+/// A convex body shape centred on its local origin
+pub trait Shape {
+    fn area(&self) -> Scalar;
+
+    /// Resistance to turning about the centre for a body of the given mass
+    fn moment_of_inertia(&self, mass: Scalar) -> Scalar;
+
+    /// Smallest axis-aligned box around the shape once placed by `transform`, as (min, max)
+    fn aabb(&self, transform: &Transform) -> (Vector2, Vector2);
+
+    /// Point of the shape furthest along `direction`, both in local space
+    fn support(&self, direction: Vector2) -> Vector2;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub radius: Scalar,
+}
+
+impl Shape for Circle {
+    fn area(&self) -> Scalar {
+        consts::PI * self.radius * self.radius
+    }
+
+    fn moment_of_inertia(&self, mass: Scalar) -> Scalar {
+        0.5 * mass * self.radius * self.radius
+    }
+
+    fn aabb(&self, transform: &Transform) -> (Vector2, Vector2) {
+        let extent = Vector2::splat(self.radius);
+        (transform.position - extent, transform.position + extent)
+    }
+
+    fn support(&self, direction: Vector2) -> Vector2 {
+        direction.normalize_or_zero() * self.radius
+    }
+}
+
+/// A box, axis-aligned in its own local space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub half_extents: Vector2,
+}
+
+impl Shape for Aabb {
+    fn area(&self) -> Scalar {
+        4.0 * self.half_extents.x * self.half_extents.y
+    }
+
+    fn moment_of_inertia(&self, mass: Scalar) -> Scalar {
+        mass * self.half_extents.length_squared() / 3.0
+    }
+
+    fn aabb(&self, transform: &Transform) -> (Vector2, Vector2) {
+        // The rotated box's extent along each axis is the sum of its turned half extents
+        let rotation = Vector2::from_angle(transform.rotation);
+        let extent = Vector2::new(
+            (rotation.x * self.half_extents.x).abs() + (rotation.y * self.half_extents.y).abs(),
+            (rotation.y * self.half_extents.x).abs() + (rotation.x * self.half_extents.y).abs(),
+        );
+        (transform.position - extent, transform.position + extent)
+    }
+
+    fn support(&self, direction: Vector2) -> Vector2 {
+        Vector2::new(
+            self.half_extents.x.copysign(direction.x),
+            self.half_extents.y.copysign(direction.y),
+        )
+    }
+}
+
+/// A convex polygon, its vertices given anticlockwise around the local origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolygon {
+    pub vertices: Vec<Vector2>,
+}
+
+impl ConvexPolygon {
+    fn edges(&self) -> impl Iterator<Item = (Vector2, Vector2)> + '_ {
+        let next = self.vertices.iter().cycle().skip(1);
+        self.vertices.iter().copied().zip(next.copied())
+    }
+}
+
+impl Shape for ConvexPolygon {
+    fn area(&self) -> Scalar {
+        0.5 * self
+            .edges()
+            .map(|(a, b)| a.perp_dot(b))
+            .sum::<Scalar>()
+            .abs()
+    }
+
+    fn moment_of_inertia(&self, mass: Scalar) -> Scalar {
+        // Sum over the triangles fanning out from the origin, each weighted by its area
+        let (weighted, total) = self.edges().fold((0.0, 0.0), |(weighted, total), (a, b)| {
+            let cross = a.perp_dot(b).abs();
+            (
+                weighted + cross * (a.dot(a) + a.dot(b) + b.dot(b)),
+                total + cross,
+            )
+        });
+
+        if total == 0.0 {
+            0.0
+        } else {
+            mass * weighted / (6.0 * total)
+        }
+    }
+
+    fn aabb(&self, transform: &Transform) -> (Vector2, Vector2) {
+        self.vertices.iter().fold(
+            (
+                Vector2::splat(Scalar::INFINITY),
+                Vector2::splat(Scalar::NEG_INFINITY),
+            ),
+            |(min, max), &vertex| {
+                let vertex = transform.apply(vertex);
+                (min.min(vertex), max.max(vertex))
+            },
+        )
+    }
+
+    fn support(&self, direction: Vector2) -> Vector2 {
+        self.vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap_or(Vector2::ZERO)
+    }
+}
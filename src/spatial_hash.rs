@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::scalar::{Scalar, Vector2};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct CellCoords(i32, i32);
+
+// Half of the surrounding cells, chosen so that no two cells list each other
+const FORWARD_NEIGHBOURS: [(i32, i32); 4] = [(1, -1), (1, 0), (1, 1), (0, 1)];
+
+#[derive(Debug)]
+
+pub struct SpatialHash<ID> {
+    // Width and height of each cell
+    cell_dims: Vector2,
+    grid: HashMap<CellCoords, Vec<ID>>, // Mapping of cell coordinates to object IDs
+    cells: HashMap<ID, CellCoords>,     // Reverse index of which cell each object ID is in
+    // Smallest and largest cell coordinates holding an object since the last clear. Removals
+    // don't shrink it, so it can only be too big, never miss an object
+    bounds: Option<(CellCoords, CellCoords)>,
+}
+
+impl<ID: Clone + Eq + Hash> SpatialHash<ID> {
+    /// Creates a new SpatialHash with the given cell size
+    pub fn new(cell_size: Scalar) -> Self {
+        Self::new_anisotropic(cell_size, cell_size)
+    }
+
+    /// Creates a new SpatialHash whose cells are `cell_width` wide and `cell_height` tall, for
+    /// worlds or bodies much longer along one axis than the other
+    pub fn new_anisotropic(cell_width: Scalar, cell_height: Scalar) -> Self {
+        Self {
+            cell_dims: Vector2::new(cell_width, cell_height),
+            grid: HashMap::new(),
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+
+    /// Converts a position vector to a cell coordinate
+    fn to_cell_coords(&self, position: Vector2) -> CellCoords {
+        CellCoords(
+            (position.x / self.cell_dims.x).floor() as i32,
+            (position.y / self.cell_dims.y).floor() as i32,
+        )
+    }
+
+    /// Inserts an object ID into the spatial hash
+    ///
+    /// The ID must not be stored already, or it would be returned twice as a neighbour. Use
+    /// `update` for IDs that may already be present. Debug builds panic on a duplicate
+    pub fn insert(&mut self, position: Vector2, id: ID) {
+        let cell_coords = self.to_cell_coords(position);
+        self.grid.entry(cell_coords).or_default().push(id.clone());
+
+        let CellCoords(x, y) = cell_coords;
+        self.bounds = Some(match self.bounds {
+            Some((CellCoords(min_x, min_y), CellCoords(max_x, max_y))) => (
+                CellCoords(min_x.min(x), min_y.min(y)),
+                CellCoords(max_x.max(x), max_y.max(y)),
+            ),
+            None => (cell_coords, cell_coords),
+        });
+
+        let previous = self.cells.insert(id, cell_coords);
+        debug_assert!(
+            previous.is_none(),
+            "ID inserted into the spatial hash twice, use update to move it instead"
+        );
+    }
+
+    /// Removes an object ID from whichever cell it was last inserted into
+    pub fn remove_by_id(&mut self, id: ID) {
+        let Some(cell_coords) = self.cells.remove(&id) else {
+            return;
+        };
+
+        if let Some(cell) = self.grid.get_mut(&cell_coords) {
+            if let Some(pos) = cell.iter().position(|stored_id| *stored_id == id) {
+                cell.swap_remove(pos);
+            }
+
+            if cell.is_empty() {
+                self.grid.remove(&cell_coords);
+            }
+        }
+    }
+
+    /// Moves an object ID to the cell for its new position, inserting it if it isn't stored yet
+    ///
+    /// Returns whether it had to be moved or inserted, false if it stayed in the same cell
+    pub fn update(&mut self, id: ID, position: Vector2) -> bool {
+        if self.cells.get(&id) == Some(&self.to_cell_coords(position)) {
+            return false;
+        }
+
+        self.remove_by_id(id.clone());
+        self.insert(position, id);
+        true
+    }
+
+    /// Updates a batch of objects, only touching the ones that have crossed into a new cell, and
+    /// returns how many that was
+    pub fn update_all(&mut self, positions: &[(ID, Vector2)]) -> usize {
+        let mut moved = 0;
+        for (id, position) in positions {
+            if self.update(id.clone(), *position) {
+                moved += 1;
+            }
+        }
+        moved
+    }
+
+    // Removes an object ID from the spatial hash
+    // pub fn remove(&mut self, position: Vector2, id: ID) {
+    //     if let Some(cell) = self.grid.get_mut(&self.to_cell_coords(position)) {
+    //         if let Some(pos) = cell.iter().position(|&stored_id| stored_id == id) {
+    //             cell.remove(pos);
+    //         }
+    //     }
+    // }
+
+    pub fn clear(&mut self) {
+        self.grid.clear();
+        self.cells.clear();
+        self.bounds = None;
+    }
+
+    /// Side of the cells, the shorter one if they aren't square
+    pub fn cell_size(&self) -> Scalar {
+        self.cell_dims.min_element()
+    }
+
+    /// Width and height of the cells
+    pub fn cell_dims(&self) -> Vector2 {
+        self.cell_dims
+    }
+
+    /// Changes the cells to squares of `cell_size` and empties the hash, so every object must be inserted again
+    ///
+    /// Panics if `cell_size` isn't positive, since every position would then map to the same or
+    /// to no cell
+    pub fn set_cell_size(&mut self, cell_size: Scalar) {
+        assert!(
+            cell_size > 0.0,
+            "cell_size must be positive, got {}",
+            cell_size
+        );
+
+        self.cell_dims = Vector2::splat(cell_size);
+        self.clear();
+    }
+
+    /// Number of cells holding at least one object
+    pub fn occupied_cells(&self) -> usize {
+        self.grid.len()
+    }
+
+    /// Occupied cells per stored object, approaching 1 when nearly every object has a cell to itself
+    pub fn occupancy_ratio(&self) -> Scalar {
+        if self.cells.is_empty() {
+            0.0
+        } else {
+            self.grid.len() as Scalar / self.cells.len() as Scalar
+        }
+    }
+
+    // Returns a list of object IDs in the specified cell
+    // pub fn get_objects_in_cell(&self, position: Vector2) -> Option<&Vec<ID>> {
+    //     let cell_coords = self.to_cell_coords(position);
+    //     self.grid.get(&cell_coords)
+    // }
+
+    /// Yields every pair of objects in the same or adjacent cells exactly once
+    ///
+    /// Each cell is paired with itself and the forward half of its neighbours, so the
+    /// mirrored pair from the other cell is never produced. Cells are visited in order of their
+    /// coordinates rather than the hash map's, so the pairs come out in the same order on every
+    /// run and toolchain
+    pub fn candidate_pairs(&self) -> impl Iterator<Item = (ID, ID)> + '_ {
+        let mut occupied: Vec<CellCoords> = self.grid.keys().copied().collect();
+        occupied.sort_unstable();
+
+        occupied.into_iter().flat_map(move |cell_coords| {
+            let objects = &self.grid[&cell_coords];
+            let within_cell = objects.iter().enumerate().flat_map(move |(i, id)| {
+                objects[i + 1..]
+                    .iter()
+                    .map(move |other_id| (id.clone(), other_id.clone()))
+            });
+
+            let across_cells = FORWARD_NEIGHBOURS
+                .iter()
+                .filter_map(move |&(dx, dy)| {
+                    self.grid
+                        .get(&CellCoords(cell_coords.0 + dx, cell_coords.1 + dy))
+                })
+                .flat_map(move |neighbours| {
+                    objects.iter().flat_map(move |id| {
+                        neighbours
+                            .iter()
+                            .map(move |other_id| (id.clone(), other_id.clone()))
+                    })
+                });
+
+            within_cell.chain(across_cells)
+        })
+    }
+
+    /// Returns a list of object IDs within the surrounding cells
+    ///
+    /// Each ID is stored in exactly one cell, the one holding its position, and every cell is
+    /// visited once, so the list never holds the same ID twice and needs no deduplication
+    pub fn get_nearby_objects(&self, position: Vector2, id: ID) -> Vec<ID> {
+        let center_cell = self.to_cell_coords(position);
+
+        let mut nearby_objects = Vec::new();
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let cell_coords = CellCoords(center_cell.0 + dx, center_cell.1 + dy);
+
+                if let Some(objects) = self.grid.get(&cell_coords) {
+                    nearby_objects.extend(
+                        objects
+                            .iter()
+                            .filter(|object_id| **object_id != id)
+                            .cloned(),
+                    );
+                }
+            }
+        }
+
+        nearby_objects
+    }
+
+    /// Returns the object IDs in every cell that reaches within `range` of `position`
+    ///
+    /// Unlike the square of cells `get_nearby_objects` visits, the corner cells lying wholly
+    /// outside the circle are skipped, which saves the most when `range` spans several cells.
+    /// Objects in a visited cell can still be up to a cell diagonal beyond `range`, so callers
+    /// check the exact distance themselves
+    pub fn get_nearby_objects_circular(&self, position: Vector2, range: Scalar) -> Vec<ID> {
+        let center_cell = self.to_cell_coords(position);
+        let reach_x = (range / self.cell_dims.x).ceil() as i32;
+        let reach_y = (range / self.cell_dims.y).ceil() as i32;
+
+        let mut nearby_objects = Vec::new();
+
+        for dx in -reach_x..=reach_x {
+            for dy in -reach_y..=reach_y {
+                let cell_coords = CellCoords(center_cell.0 + dx, center_cell.1 + dy);
+
+                let cell_min =
+                    Vector2::new(cell_coords.0 as Scalar, cell_coords.1 as Scalar) * self.cell_dims;
+                let nearest = position.clamp(cell_min, cell_min + self.cell_dims);
+                if nearest.distance_squared(position) > range * range {
+                    continue;
+                }
+
+                if let Some(objects) = self.grid.get(&cell_coords) {
+                    nearby_objects.extend(objects.iter().cloned());
+                }
+            }
+        }
+
+        nearby_objects
+    }
+
+    /// Returns the object closest to `position`, given where each object is, or `None` if the
+    /// hash is empty
+    ///
+    /// Searches rings of cells outwards from the one holding `position`, and stops once no
+    /// unsearched ring can hold anything closer than the best found so far. That is usually
+    /// one ring past the first hit, since the corner of a ring can be further away than the
+    /// middle of the next. Only the parts of rings that overlap the occupied cells are
+    /// searched, so a query far outside them costs no more than one inside
+    pub fn nearest(&self, position: Vector2, position_of: impl Fn(&ID) -> Vector2) -> Option<ID> {
+        let (min, max) = self.bounds?;
+        let center_cell = self.to_cell_coords(position);
+        let cell_size = self.cell_size();
+        let mut nearest: Option<(Scalar, ID)> = None;
+        let mut visited = 0;
+
+        // Rings closer than the occupied cells are empty, and those past their far corner too
+        let CellCoords(x, y) = center_cell;
+        let reach = |low: i32, high: i32, at: i32| (low - at).max(at - high).max(0);
+        let first_ring = reach(min.0, max.0, x).max(reach(min.1, max.1, y));
+        let last_ring = (x - min.0)
+            .abs()
+            .max((max.0 - x).abs())
+            .max((y - min.1).abs())
+            .max((max.1 - y).abs());
+
+        for ring in first_ring..=last_ring {
+            // `position` lies in the centre cell, so everything in this ring is at least this far
+            let ring_distance = (ring - 1).max(0) as Scalar * cell_size;
+            let done = nearest.as_ref().is_some_and(|(nearest_squared, _)| {
+                *nearest_squared < ring_distance * ring_distance
+            });
+            if done || visited == self.cells.len() {
+                break;
+            }
+
+            for cell_coords in ring_cells(center_cell, ring, (min, max)) {
+                let Some(objects) = self.grid.get(&cell_coords) else {
+                    continue;
+                };
+
+                for id in objects {
+                    let distance_squared = position_of(id).distance_squared(position);
+                    if nearest
+                        .as_ref()
+                        .is_none_or(|(nearest_squared, _)| distance_squared < *nearest_squared)
+                    {
+                        nearest = Some((distance_squared, id.clone()));
+                    }
+                }
+                visited += objects.len();
+            }
+        }
+
+        nearest.map(|(_, id)| id)
+    }
+}
+
+/// The cells exactly `ring` cells from `center` along either axis, the square's outline, that
+/// lie within `bounds`
+fn ring_cells(
+    center: CellCoords,
+    ring: i32,
+    bounds: (CellCoords, CellCoords),
+) -> impl Iterator<Item = CellCoords> {
+    let CellCoords(x, y) = center;
+    let (CellCoords(min_x, min_y), CellCoords(max_x, max_y)) = bounds;
+
+    // The top and bottom rows in full, then the left and right columns between them
+    let rows = [Some(y - ring), (ring > 0).then_some(y + ring)]
+        .into_iter()
+        .flatten()
+        .filter(move |row_y| (min_y..=max_y).contains(row_y))
+        .flat_map(move |row_y| {
+            ((x - ring).max(min_x)..=(x + ring).min(max_x))
+                .map(move |cell_x| CellCoords(cell_x, row_y))
+        });
+
+    let columns = (ring > 0)
+        .then_some([x - ring, x + ring])
+        .into_iter()
+        .flatten()
+        .filter(move |column_x| (min_x..=max_x).contains(column_x))
+        .flat_map(move |column_x| {
+            ((y - ring + 1).max(min_y)..=(y + ring - 1).min(max_y))
+                .map(move |cell_y| CellCoords(column_x, cell_y))
+        });
+
+    rows.chain(columns)
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use macroquad::prelude::*;
+use glam::Vec3;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct CellCoords(i32, i32, i32);
@@ -8,15 +8,21 @@ struct CellCoords(i32, i32, i32);
 #[derive(Debug)]
 
 pub struct SpatialHash<ID> {
-    cell_size: f32,
+    // Width, height and depth of each cell
+    cell_dims: Vec3,
     grid: HashMap<CellCoords, Vec<ID>>, // Mapping of cell coordinates to object IDs
 }
 
-impl<ID: Copy + Eq> SpatialHash<ID> {
+impl<ID: Clone + Eq> SpatialHash<ID> {
     /// Creates a new SpatialHash with the given cell size
     pub fn new(cell_size: f32) -> Self {
+        Self::new_anisotropic(cell_size, cell_size, cell_size)
+    }
+
+    /// Creates a new SpatialHash with separate cell dimensions along each axis
+    pub fn new_anisotropic(cell_width: f32, cell_height: f32, cell_depth: f32) -> Self {
         Self {
-            cell_size,
+            cell_dims: Vec3::new(cell_width, cell_height, cell_depth),
             grid: HashMap::new(),
         }
     }
@@ -24,22 +30,19 @@ impl<ID: Copy + Eq> SpatialHash<ID> {
     /// Converts a position vector to a cell coordinate
     fn to_cell_coords(&self, position: Vec3) -> CellCoords {
         CellCoords(
-            (position.x / self.cell_size).floor() as i32,
-            (position.y / self.cell_size).floor() as i32,
-            (position.z / self.cell_size).floor() as i32,
+            (position.x / self.cell_dims.x).floor() as i32,
+            (position.y / self.cell_dims.y).floor() as i32,
+            (position.z / self.cell_dims.z).floor() as i32,
         )
     }
 
     /// Inserts an object ID into the spatial hash
     pub fn insert(&mut self, position: Vec3, id: ID) {
         let cell_coords = self.to_cell_coords(position);
-        self.grid
-            .entry(cell_coords)
-            .or_insert_with(Vec::new)
-            .push(id);
+        self.grid.entry(cell_coords).or_default().push(id);
     }
 
-    /// Removes an object ID from the spatial hash
+    // Removes an object ID from the spatial hash
     // pub fn remove(&mut self, position: Vec3, id: ID) {
     //     if let Some(cell) = self.grid.get_mut(&self.to_cell_coords(position)) {
     //         if let Some(pos) = cell.iter().position(|&stored_id| stored_id == id) {
@@ -52,14 +55,23 @@ impl<ID: Copy + Eq> SpatialHash<ID> {
         self.grid.clear();
     }
 
-    /// Returns a list of object IDs in the specified cell
+    // Returns a list of object IDs in the specified cell
     // pub fn get_objects_in_cell(&self, position: Vec3) -> Option<&Vec<ID>> {
     //     let cell_coords = self.to_cell_coords(position);
     //     self.grid.get(&cell_coords)
     // }
 
-    /// Returns a list of object IDs within the surrounding cells
+    /// Returns a list of object IDs within the surrounding cells, leaving out `id` itself
     pub fn get_nearby_objects(&self, position: Vec3, id: ID) -> Vec<ID> {
+        let mut nearby_objects = self.get_nearby_objects_at(position);
+        nearby_objects.retain(|object_id| *object_id != id);
+
+        nearby_objects
+    }
+
+    /// Returns a list of object IDs within the cells surrounding a point that isn't itself an
+    /// object, such as the cursor
+    pub fn get_nearby_objects_at(&self, position: Vec3) -> Vec<ID> {
         let center_cell = self.to_cell_coords(position);
 
         let mut nearby_objects = Vec::new();
@@ -71,8 +83,7 @@ impl<ID: Copy + Eq> SpatialHash<ID> {
                         CellCoords(center_cell.0 + dx, center_cell.1 + dy, center_cell.2 + dz);
 
                     if let Some(objects) = self.grid.get(&cell_coords) {
-                        nearby_objects
-                            .extend(objects.iter().copied().filter(|&object_id| object_id != id));
+                        nearby_objects.extend(objects.iter().cloned());
                     }
                 }
             }
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use macroquad::prelude::Color;
+
+use super::world::{Ball, BodyKind};
+use crate::scalar::{to_f32, Scalar, Vector2};
+
+const COLUMNS: [&str; 8] = ["x", "y", "vx", "vy", "radius", "r", "g", "b"];
+
+/// Why a ball CSV couldn't be loaded
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    /// A row that doesn't hold eight numbers making a valid ball, numbered from 1
+    Row {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(error) => write!(f, "{}", error),
+            CsvError::Row { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(error: io::Error) -> Self {
+        CsvError::Io(error)
+    }
+}
+
+/// Reads balls from a CSV file with the columns `x,y,vx,vy,radius,r,g,b`
+///
+/// See [`parse_balls_csv`] for the format
+pub fn load_balls_csv(path: &str) -> Result<Vec<Ball>, CsvError> {
+    parse_balls_csv(&fs::read_to_string(path)?)
+}
+
+/// Reads one ball per row of `x,y,vx,vy,radius,r,g,b`, with the colour channels from 0 to 1
+///
+/// A first row that doesn't start with a number is taken as a header and skipped, as are
+/// blank lines. The balls are dynamic, numbered in row order, with normal gravity and no
+/// speed cap
+pub fn parse_balls_csv(text: &str) -> Result<Vec<Ball>, CsvError> {
+    let mut balls = Vec::new();
+
+    for (index, row) in text.lines().enumerate() {
+        let line = index + 1;
+        let row = row.trim();
+        if row.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        if line == 1 && fields[0].parse::<Scalar>().is_err() {
+            continue;
+        }
+
+        let error = |message: String| CsvError::Row { line, message };
+        if fields.len() != COLUMNS.len() {
+            return Err(error(format!(
+                "expected {} columns ({}), found {}",
+                COLUMNS.len(),
+                COLUMNS.join(","),
+                fields.len()
+            )));
+        }
+
+        let mut values = [0.0; 8];
+        for ((value, field), column) in values.iter_mut().zip(&fields).zip(COLUMNS) {
+            *value = field
+                .parse::<Scalar>()
+                .map_err(|_| error(format!("{} is not a number: {:?}", column, field)))?;
+        }
+
+        let [x, y, vx, vy, radius, r, g, b] = values;
+        if !radius.is_finite() || radius <= 0.0 {
+            return Err(error(format!("radius must be positive, got {}", radius)));
+        }
+
+        let position = Vector2::new(x, y);
+        balls.push(Ball {
+            id: balls.len(),
+            position,
+            velocity: Vector2::new(vx, vy),
+            pressure: 0.0,
+            color: Color::new(to_f32(r), to_f32(g), to_f32(b), 1.0),
+            radius,
+            acceleration: Vector2::ZERO,
+            kind: BodyKind::Dynamic,
+            age: 0.0,
+            lifetime: None,
+            previous_position: position,
+            gravity_scale: 1.0,
+            max_speed: Scalar::INFINITY,
+            user_data: 0,
+            material_id: 0,
+        });
+    }
+
+    Ok(balls)
+}
@@ -0,0 +1,127 @@
+use super::world::World2D;
+use crate::common::config::{BoundaryMode, Config, GravityFalloff, InitLayout, ResolveMode, Wall};
+
+/// Builds a `World2D` from the built-in defaults, so only the settings that differ need setting,
+/// e.g. `World2D::builder().ball_count(500).gravity(9.81).build()`
+#[derive(Debug, Clone, Default)]
+pub struct WorldBuilder {
+    config: Config,
+}
+
+impl WorldBuilder {
+    /// Starts from `config` instead of the built-in defaults
+    pub fn from_config(config: Config) -> Self {
+        Self { config }
+    }
+
+    pub fn ball_count(mut self, ball_count: usize) -> Self {
+        self.config.ball_count_2d = ball_count;
+        self
+    }
+
+    /// Fills this fraction of the area with balls instead of using `ball_count`
+    pub fn fill_fraction(mut self, fill_fraction: f32) -> Self {
+        self.config.fill_fraction = Some(fill_fraction);
+        self
+    }
+
+    pub fn ball_radius(mut self, ball_radius: f32) -> Self {
+        self.config.ball_radius = ball_radius;
+        self
+    }
+
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    /// Downward acceleration in units/s²
+    pub fn gravity(mut self, gravity: f32) -> Self {
+        self.config.gravity = gravity;
+        self
+    }
+
+    pub fn gravity_falloff(mut self, gravity_falloff: GravityFalloff) -> Self {
+        self.config.gravity_falloff = gravity_falloff;
+        self
+    }
+
+    pub fn resistance(mut self, resistance: f32) -> Self {
+        self.config.resistance = resistance;
+        self
+    }
+
+    pub fn bounce_amount(mut self, bounce_amount: f32) -> Self {
+        self.config.bounce_amount = bounce_amount;
+        self
+    }
+
+    pub fn max_speed(mut self, max_speed: f32) -> Self {
+        self.config.max_speed = max_speed;
+        self
+    }
+
+    pub fn resolve_mode(mut self, resolve_mode: ResolveMode) -> Self {
+        self.config.resolve_mode = resolve_mode;
+        self
+    }
+
+    /// Solver iterations per step
+    pub fn sim_steps(mut self, sim_steps: i32) -> Self {
+        self.config.sim_steps = sim_steps;
+        self
+    }
+
+    pub fn layout(mut self, init_layout: InitLayout, layout_gap: f32) -> Self {
+        self.config.init_layout = init_layout;
+        self.config.layout_gap = layout_gap;
+        self
+    }
+
+    pub fn boundary_mode(mut self, boundary_mode: BoundaryMode) -> Self {
+        self.config.boundary_mode = boundary_mode;
+        self
+    }
+
+    pub fn walls(mut self, walls: [Wall; 4]) -> Self {
+        self.config.walls_2d = walls;
+        self
+    }
+
+    pub fn cohesion(mut self, cohesion_strength: f32, interaction_radius: f32) -> Self {
+        self.config.cohesion_strength = cohesion_strength;
+        self.config.interaction_radius = interaction_radius;
+        self
+    }
+
+    pub fn island_solver(mut self, island_solver: bool) -> Self {
+        self.config.island_solver = island_solver;
+        self
+    }
+
+    pub fn parallel_solver(mut self, parallel_solver: bool) -> Self {
+        self.config.parallel_solver = parallel_solver;
+        self
+    }
+
+    pub fn adaptive_substeps(mut self, adaptive_substeps: bool) -> Self {
+        self.config.adaptive_substeps = adaptive_substeps;
+        self
+    }
+
+    /// Changes any other setting, for those without a setter of their own
+    pub fn configure(mut self, change: impl FnOnce(&mut Config)) -> Self {
+        change(&mut self.config);
+        self
+    }
+
+    /// The config the world will be built from
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn build(self) -> World2D {
+        World2D::new(&self.config)
+    }
+}
@@ -0,0 +1,25 @@
+/// Splits `pairs` into colour classes where no two pairs in a class share a ball, so every pair
+/// in a class can be resolved at the same time without touching the same ball twice
+///
+/// Greedy: each pair takes the lowest colour neither of its balls has used yet, which keeps the
+/// class count close to the most contacts any one ball has. Classes keep the input order
+pub fn color_pairs(ball_count: usize, pairs: &[(usize, usize)]) -> Vec<Vec<(usize, usize)>> {
+    let mut used: Vec<Vec<usize>> = vec![Vec::new(); ball_count];
+    let mut classes: Vec<Vec<(usize, usize)>> = Vec::new();
+
+    for &(id, other_id) in pairs {
+        let color = (0..)
+            .find(|color| !used[id].contains(color) && !used[other_id].contains(color))
+            .unwrap();
+
+        if color == classes.len() {
+            classes.push(Vec::new());
+        }
+
+        classes[color].push((id, other_id));
+        used[id].push(color);
+        used[other_id].push(color);
+    }
+
+    classes
+}
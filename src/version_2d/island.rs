@@ -0,0 +1,78 @@
+/// A group of balls connected through contacts, which can be solved without looking at any other
+#[derive(Debug, Clone, Default)]
+pub struct Island {
+    pub balls: Vec<usize>,
+    pub pairs: Vec<(usize, usize)>,
+}
+
+/// Union-find over ball ids, with path halving and union by size
+struct DisjointSet {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(count: usize) -> Self {
+        Self {
+            parents: (0..count).collect(),
+            sizes: vec![1; count],
+        }
+    }
+
+    fn find(&mut self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            self.parents[id] = self.parents[self.parents[id]];
+            id = self.parents[id];
+        }
+
+        id
+    }
+
+    fn union(&mut self, id: usize, other_id: usize) {
+        let (mut root, mut other_root) = (self.find(id), self.find(other_id));
+
+        if root == other_root {
+            return;
+        }
+
+        if self.sizes[root] < self.sizes[other_root] {
+            std::mem::swap(&mut root, &mut other_root);
+        }
+
+        self.parents[other_root] = root;
+        self.sizes[root] += self.sizes[other_root];
+    }
+}
+
+/// Splits `ball_count` balls into islands joined by `pairs`
+///
+/// Islands come out ordered by their lowest ball id, and keep the order of their balls and pairs,
+/// so the result only depends on the input
+pub fn build_islands(ball_count: usize, pairs: &[(usize, usize)]) -> Vec<Island> {
+    let mut set = DisjointSet::new(ball_count);
+
+    for &(id, other_id) in pairs {
+        set.union(id, other_id);
+    }
+
+    let mut island_of_root = vec![usize::MAX; ball_count];
+    let mut islands: Vec<Island> = Vec::new();
+
+    for id in 0..ball_count {
+        let root = set.find(id);
+
+        if island_of_root[root] == usize::MAX {
+            island_of_root[root] = islands.len();
+            islands.push(Island::default());
+        }
+
+        islands[island_of_root[root]].balls.push(id);
+    }
+
+    for &(id, other_id) in pairs {
+        let root = set.find(id);
+        islands[island_of_root[root]].pairs.push((id, other_id));
+    }
+
+    islands
+}
@@ -0,0 +1,67 @@
+use crate::rng::Rng;
+use crate::scalar::{Scalar, Vector2};
+
+// Random candidates tried per requested ball before giving up on packing any more in
+const ATTEMPTS_PER_BALL: usize = 30;
+const MAX_CELLS_PER_AXIS: Scalar = 4096.0;
+
+/// Up to `count` random centres inside `bounds`, as (min, max), for balls of `radius` that
+/// don't overlap each other or the edges
+///
+/// Candidates are drawn by rejection sampling, so fewer come back when the balls are packed
+/// too densely to find room for them all
+pub fn generate_non_overlapping(
+    count: usize,
+    bounds: (Vector2, Vector2),
+    radius: Scalar,
+    rng: &mut Rng,
+) -> Vec<Vector2> {
+    let (min, max) = (bounds.0 + radius, bounds.1 - radius);
+    if count == 0 || min.x > max.x || min.y > max.y {
+        return Vec::new();
+    }
+
+    let spacing = 2.0 * radius;
+    // Cells at least `spacing` wide, so only a candidate's own and neighbouring cells can hold
+    // a centre close enough to overlap it. Capped so a tiny radius can't ask for a huge grid
+    let columns = ((max.x - min.x) / spacing).clamp(1.0, MAX_CELLS_PER_AXIS) as usize;
+    let rows = ((max.y - min.y) / spacing).clamp(1.0, MAX_CELLS_PER_AXIS) as usize;
+    let cell_size = Vector2::new(
+        (max.x - min.x) / columns as Scalar,
+        (max.y - min.y) / rows as Scalar,
+    );
+    let cell_of = |point: Vector2| {
+        let cell = (point - min) / cell_size;
+        (
+            (cell.x as usize).min(columns - 1),
+            (cell.y as usize).min(rows - 1),
+        )
+    };
+
+    let mut grid: Vec<Vec<usize>> = vec![Vec::new(); columns * rows];
+    let mut positions: Vec<Vector2> = Vec::with_capacity(count);
+
+    for _ in 0..count * ATTEMPTS_PER_BALL {
+        if positions.len() == count {
+            break;
+        }
+
+        let candidate = Vector2::new(rng.gen_range(min.x, max.x), rng.gen_range(min.y, max.y));
+        let (col, row) = cell_of(candidate);
+
+        let clear = (row.saturating_sub(1)..(row + 2).min(rows)).all(|row| {
+            (col.saturating_sub(1)..(col + 2).min(columns)).all(|col| {
+                grid[row * columns + col]
+                    .iter()
+                    .all(|&index| positions[index].distance_squared(candidate) >= spacing * spacing)
+            })
+        });
+
+        if clear {
+            grid[row * columns + col].push(positions.len());
+            positions.push(candidate);
+        }
+    }
+
+    positions
+}
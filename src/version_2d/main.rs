@@ -1,57 +1,99 @@
 use rust_physics_engine::common;
-mod spatial_hash;
-
-use common::config::load_config;
+use rust_physics_engine::scalar::{consts, from_vec2, to_f32, to_vec2, vector2, Scalar, Vector2};
+use rust_physics_engine::version_2d::ball_csv::load_balls_csv;
+use rust_physics_engine::version_2d::metrics::{MetricsWriter, SettlingMonitor};
+use rust_physics_engine::version_2d::rewind::RewindBuffer;
+use rust_physics_engine::version_2d::svg::export_svg;
+use rust_physics_engine::version_2d::world::{
+    center_of_mass, Ball, BlackHole, DrainZone, Emitter, RenderContext, World2D,
+};
+
+use common::auto_sim_steps::{AutoSimSteps, MAX_SIM_STEPS, MIN_SIM_STEPS};
+use common::color_scale::ColorScale;
+use common::config::{load_profiles_or_default, BASE_PROFILE};
 use common::fps_counter::SmoothedFps;
-use spatial_hash::SpatialHash;
+use common::frame_limiter::FrameLimiter;
+use common::legend::draw_legend;
 
 use partial_borrow::prelude::*;
 
 use macroquad::prelude::*;
-
-#[derive(Debug, Clone, Copy)]
-struct Ball {
-    id: usize,
-    position: Vec2,
-    velocity: Vec2,
-    pressure: f32,
-    color: Color,
-    radius: f32,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use macroquad::ui::root_ui;
+
+use serde::{Deserialize, Serialize};
+
+use panel::draw_parameter_panel;
+use ui_state::{load_ui_state, save_ui_state, UiState};
+
+mod panel;
+mod ui_state;
+
+const UI_STATE_PATH: &str = "state.json";
+const CELL_SIZE_NUDGE: Scalar = 1.25; // Factor `-` and `=` shrink and grow the hash cells by
+const CONTAINER_SPEED: Scalar = 600.0; // Speed I, J, K and L move the container at, in units/s
+const CONTAINER_RETURN_RATE: Scalar = 4.0; // Fraction of its offset the container closes per second
+const CONTAINER_KEYS: [(KeyCode, Vec2); 4] = [
+    (KeyCode::J, vec2(-1.0, 0.0)),
+    (KeyCode::L, vec2(1.0, 0.0)),
+    (KeyCode::I, vec2(0.0, -1.0)),
+    (KeyCode::K, vec2(0.0, 1.0)),
+];
+const VELOCITY_ARROW_TIME: Scalar = 0.1; // Seconds ahead the velocity arrows point
+const STRETCH_PER_SPEED: f32 = 0.0005; // Stretch added per unit/s of speed in squash and stretch
+const MAX_STRETCH: f32 = 1.6; // Most a ball is ever stretched by, so fast ones stay round-ish
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 enum DisplayMode {
     Normal,
     Velocity,
     Pressure,
+    Acceleration,
 }
 
+// Number keys that jump straight to a display mode, in the same order `D` cycles through them
+const DISPLAY_MODE_KEYS: [(KeyCode, DisplayMode); 4] = [
+    (KeyCode::Key1, DisplayMode::Normal),
+    (KeyCode::Key2, DisplayMode::Velocity),
+    (KeyCode::Key3, DisplayMode::Pressure),
+    (KeyCode::Key4, DisplayMode::Acceleration),
+];
+
 struct State {
     display_mode: DisplayMode,
+    /// Ball shown in the inspector panel
+    selected: Option<usize>,
+    show_center_of_mass: bool,
+    show_velocity: bool,
+    /// Draw moving balls stretched along their velocity, without changing their physics
+    squash_stretch: bool,
 }
 
 impl State {
     fn new() -> Self {
         State {
             display_mode: DisplayMode::Normal,
+            selected: None,
+            show_center_of_mass: false,
+            show_velocity: false,
+            squash_stretch: false,
         }
     }
 
+    fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+    }
+
     fn toggle_display_mode(&mut self) {
         self.display_mode = match self.display_mode {
             DisplayMode::Normal => DisplayMode::Velocity,
             DisplayMode::Velocity => DisplayMode::Pressure,
-            DisplayMode::Pressure => DisplayMode::Normal,
+            DisplayMode::Pressure => DisplayMode::Acceleration,
+            DisplayMode::Acceleration => DisplayMode::Normal,
         };
     }
 }
 
-fn get_color_from_vel(ball: Ball, largest_speed: f32) -> Color {
-    let vel = ball.velocity;
-    let speed = vel.length();
-
-    let normalised_speed = speed / largest_speed;
-
+fn velocity_gradient(normalised_speed: f32) -> Color {
     Color {
         r: (0.0),
         g: (normalised_speed),
@@ -60,15 +102,7 @@ fn get_color_from_vel(ball: Ball, largest_speed: f32) -> Color {
     }
 }
 
-fn get_color_from_pressure(ball: Ball, largest_pressure: f32) -> Color {
-    let pressure = ball.pressure;
-
-    let mut normalised_pressure = 0.0;
-
-    if largest_pressure != 0.0 {
-        normalised_pressure = pressure / largest_pressure;
-    }
-
+fn pressure_gradient(normalised_pressure: f32) -> Color {
     Color {
         r: (normalised_pressure),
         g: (0.0),
@@ -77,212 +111,323 @@ fn get_color_from_pressure(ball: Ball, largest_pressure: f32) -> Color {
     }
 }
 
-fn is_colliding(ball: &Ball, otherball: &Ball) -> bool {
-    let dist = ball.position.distance(otherball.position);
-
-    dist < ball.radius + otherball.radius
+fn acceleration_gradient(normalised_acceleration: f32) -> Color {
+    Color {
+        r: (normalised_acceleration),
+        g: (1.0 - normalised_acceleration),
+        b: (0.0),
+        a: (1.0),
+    }
 }
 
-fn resolve_collision(ball: &mut Ball, otherball: &mut Ball, bounce_amount: f32, max_pressure: f32) {
-    let mut pdiff = otherball.position - ball.position;
+fn get_color_from_vel(ball: Ball, largest_speed: Scalar) -> Color {
+    let vel = ball.velocity;
+    let speed = vel.length();
+
+    let normalised_speed = speed / largest_speed;
+
+    velocity_gradient(to_f32(normalised_speed))
+}
 
-    let dist = ball.position.distance(otherball.position);
+fn get_color_from_pressure(ball: Ball, pressure_scale: Scalar) -> Color {
+    let pressure = ball.pressure;
 
-    let overlap = (ball.radius + otherball.radius) - dist;
+    let mut normalised_pressure = 0.0;
 
-    if overlap < 0.001 {
-        return;
+    if pressure_scale != 0.0 {
+        normalised_pressure = (pressure / pressure_scale).min(1.0);
     }
 
-    pdiff /= dist;
+    pressure_gradient(to_f32(normalised_pressure))
+}
 
-    ball.position -= pdiff * overlap / 2.0;
-    otherball.position += pdiff * overlap / 2.0;
+fn get_color_from_acceleration(ball: Ball, largest_acceleration: Scalar) -> Color {
+    let acceleration = ball.acceleration.length();
 
-    let relative_velocity = otherball.velocity - ball.velocity;
-    let dot_product = relative_velocity.dot(pdiff);
+    let mut normalised_acceleration = 0.0;
 
-    if dot_product > 0.0 {
-        return;
+    if largest_acceleration != 0.0 {
+        normalised_acceleration = acceleration / largest_acceleration;
     }
 
-    let force = dot_product * bounce_amount;
-
-    ball.pressure = (ball.pressure + -force / (std::f32::consts::PI * ball.radius * ball.radius))
-        .min(1.0)
-        .max(0.0);
-    otherball.pressure = (otherball.pressure
-        + -force / (std::f32::consts::PI * otherball.radius * otherball.radius))
-        .min(1.0)
-        .max(0.0);
+    acceleration_gradient(to_f32(normalised_acceleration))
+}
 
-    ball.velocity += pdiff * force;
-    otherball.velocity -= pdiff * force;
+/// Lines describing everything the inspector shows about ball `id`
+fn describe_ball(world: &World2D, id: usize) -> Vec<String> {
+    let ball = world.balls[id];
+
+    vec![
+        format!("ID: {}", ball.id),
+        format!("POSITION: ({:.2}, {:.2})", ball.position.x, ball.position.y),
+        format!("VELOCITY: ({:.2}, {:.2})", ball.velocity.x, ball.velocity.y),
+        format!("PRESSURE: {:.4}", ball.pressure),
+        format!("NEIGHBOURS: {}", world.neighbour_count(id)),
+        format!("CONTACTS: {:?}", world.contact_ids(id)),
+    ]
 }
 
-fn resolve_boundaries(ball: &mut Ball, screen_width: f32, screen_height: f32, bounce_amount: f32) {
-    if ball.position.x - ball.radius < 0.0 {
-        ball.position.x = ball.radius;
-        if ball.velocity.x < 0.0 {
-            ball.velocity.x *= -bounce_amount;
-        }
-    } else if ball.position.x + ball.radius > screen_width {
-        ball.position.x = screen_width - ball.radius;
-        if ball.velocity.x > 0.0 {
-            ball.velocity.x *= -bounce_amount;
-        }
+fn draw_inspector(lines: &[String]) {
+    let line_height = 20.0;
+    let width = 320.0;
+    let height = line_height * lines.len() as f32 + 10.0;
+    let y = screen_height() - height - 10.0;
+
+    draw_rectangle(10.0, y, width, height, Color::new(0.0, 0.0, 0.0, 0.7));
+    draw_rectangle_lines(10.0, y, width, height, 1.0, WHITE);
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(line, 15.0, y + line_height * (i as f32 + 1.0), 20.0, WHITE);
     }
+}
 
-    if ball.position.y - ball.radius < 0.0 {
-        ball.position.y = ball.radius;
-        if ball.velocity.y < 0.0 {
-            ball.velocity.y *= -bounce_amount;
-        }
-    } else if ball.position.y + ball.radius > screen_height {
-        ball.position.y = screen_height - ball.radius;
-        if ball.velocity.y > 0.0 {
-            ball.velocity.y *= -bounce_amount;
-        }
+/// Camera showing the world around `target`, with y pointing down like screen space
+fn world_camera(target: Vec2, zoom: f32, screen_width: f32, screen_height: f32) -> Camera2D {
+    Camera2D {
+        target,
+        zoom: vec2(2.0 * zoom / screen_width, 2.0 * zoom / screen_height),
+        ..Default::default()
     }
 }
 
 #[macroquad::main("Physics Sim")]
 // #[cfg(feature = "version_2d")]
 async fn main() {
-    let config = load_config("config.toml");
-
-    let ball_count = config.ball_count_2d;
-    let ball_radius = config.ball_radius;
-    let gravity = config.gravity;
-    let resistance = config.resistance;
-    let bounce_amount = config.bounce_amount;
-    let max_speed = config.max_speed;
-    let max_pressure = config.max_pressure;
+    let profiles = load_profiles_or_default("config.toml");
+
+    let mut profile_names: Vec<&String> = profiles.keys().collect();
+    profile_names.sort();
+
+    let active_profile = profiles[BASE_PROFILE]
+        .active_profile
+        .as_deref()
+        .unwrap_or(BASE_PROFILE);
+    let mut profile_index = profile_names
+        .iter()
+        .position(|name| name.as_str() == active_profile)
+        .unwrap_or_else(|| {
+            eprintln!(
+                "Unknown active_profile {}, using {}",
+                active_profile, BASE_PROFILE
+            );
+            profile_names
+                .iter()
+                .position(|name| name.as_str() == BASE_PROFILE)
+                .unwrap()
+        });
+
+    let config = &profiles[profile_names[profile_index]];
+
+    let args: Vec<String> = std::env::args().collect();
+    let mut metrics = args
+        .iter()
+        .position(|arg| arg == "--metrics")
+        .and_then(|index| args.get(index + 1))
+        .map(|path| MetricsWriter::create(path).expect("Failed to create metrics file"));
+
     let width = config.width;
     let height = config.height;
-    let mut sim_steps = config.sim_steps;
     let auto_sim_steps = config.auto_sim_steps;
-    let target_fps = config.target_fps;
-    let fps_boundary = config.fps_boundary;
+    let mut auto_steps = AutoSimSteps::new(config.target_fps, config.fps_boundary);
     let delete_dist = config.delete_dist;
+    let auto_pressure_scale = config.auto_pressure_scale;
+    let pressure_display_max = config.pressure_display_max;
+    let mut speed_color_scale = ColorScale::new(config.color_scale_decay);
+    let mut pressure_color_scale = ColorScale::new(config.color_scale_decay);
+    let mut acceleration_color_scale = ColorScale::new(config.color_scale_decay);
+    let emitter_rate = config.emitter_rate;
+    let emitter_spread_angle = config.emitter_spread_angle;
+    let emitter_speed = config.emitter_speed;
+    let emitter_lifetime = config.emitter_lifetime;
+    let fade_fraction = config.fade_fraction;
+    let black_hole_strength = config.black_hole_strength;
+    let black_hole_horizon = config.black_hole_horizon;
+    let black_hole_accretes = config.black_hole_accretes;
+    let (spawn_throw, spawn_velocity_spread) = (config.spawn_throw, config.spawn_velocity_spread);
+    let drain_size = vector2(config.drain_width as Scalar, config.drain_height as Scalar);
+    let fixed_timestep = config.fixed_timestep;
+    let background_color = Color::new(
+        config.background_color[0],
+        config.background_color[1],
+        config.background_color[2],
+        1.0,
+    );
 
     request_new_screen_size(width, height);
 
     let mut smoothed_fps = SmoothedFps::new();
+    let mut frame_limiter = FrameLimiter::new(config.max_fps);
+    let mut settling = SettlingMonitor::new(
+        config.settle_energy_threshold as Scalar,
+        config.settle_frames,
+    );
+
+    let mut world = World2D::new(config);
+
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--balls")
+        .and_then(|index| args.get(index + 1))
+    {
+        let balls = load_balls_csv(path)
+            .unwrap_or_else(|error| panic!("Failed to load balls from {}: {}", path, error));
+        world.replace_balls(balls);
+        world.set_max_speed(world.max_speed);
+    }
 
-    let mut colors: Vec<Color> = (0..ball_count)
-        .map(|_| {
-            Color::new(
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                1.0,
-            )
-        })
-        .collect();
-
-    let mut balls: Vec<Ball> = (0..ball_count)
-        .enumerate()
-        .map(|(id, _)| Ball {
-            id,
-            position: vec2(
-                rand::gen_range(ball_radius, width - ball_radius),
-                rand::gen_range(ball_radius, height - ball_radius),
-            ),
-            velocity: vec2(
-                rand::gen_range(-100.0, 100.0),
-                rand::gen_range(-100.0, 100.0),
-            ),
-            pressure: 0.0,
-            color: colors[id],
-            radius: ball_radius,
-        })
-        .collect();
+    let mut display_state = State::new();
 
-    let mut spatial_hash: SpatialHash<usize> = SpatialHash::new((ball_radius * 2.0) + 2.0);
+    let ui_state = load_ui_state(UI_STATE_PATH);
+    display_state.set_display_mode(ui_state.display_mode);
+    world.do_gravity = ui_state.do_gravity;
+    world.sim_steps = ui_state.sim_steps.unwrap_or(world.sim_steps);
+    let mut time_scale = ui_state.time_scale;
+    let mut paused = ui_state.paused;
+    let mut rewind = RewindBuffer::new(config.rewind_frames);
+    let mut tilting = false;
+
+    let mut cam_target = ui_state
+        .cam_target
+        .map_or(vec2(width / 2.0, height / 2.0), Vec2::from);
+    let mut cam_zoom: f32 = ui_state.cam_zoom;
+    let mut last_mouse_screen: Vec2 = mouse_position().into();
+    let mut last_mouse_position: Option<Vector2> = None;
+    let mut show_panel = false;
+    let mut svg_exports = 0;
+
+    // Quitting is handled in the loop so the UI state can be saved first
+    prevent_quit();
 
-    let mut do_gravity = true;
+    loop {
+        if is_quit_requested() {
+            if world.track_penetration {
+                println!(
+                    "peak penetration: {:.4} at {} sim steps",
+                    world.peak_penetration(),
+                    world.sim_steps
+                );
+            }
 
-    let mut display_state = State::new();
+            save_ui_state(
+                UI_STATE_PATH,
+                &UiState {
+                    display_mode: display_state.display_mode,
+                    do_gravity: world.do_gravity,
+                    sim_steps: Some(world.sim_steps),
+                    time_scale,
+                    paused,
+                    cam_target: Some(cam_target.into()),
+                    cam_zoom,
+                },
+            );
+            break;
+        }
 
-    loop {
-        clear_background(BLACK);
+        clear_background(background_color);
 
-        let mut largest_speed: f32 = 0.0;
-        let mut largest_pressure: f32 = 0.0;
+        let mut largest_speed: Scalar = 0.0;
+        let mut largest_pressure: Scalar = 0.0;
+        let mut largest_acceleration: Scalar = 0.0;
 
-        let mouse_position: Vec2 = mouse_position().into();
+        let mouse_screen: Vec2 = mouse_position().into();
+        // Clicks on the parameter panel are meant for it, not the balls underneath
+        let over_panel = show_panel && root_ui().is_mouse_over(mouse_screen);
 
         let screen_width = screen_width();
         let screen_height = screen_height();
 
-        spatial_hash.clear();
+        if is_mouse_button_down(MouseButton::Middle) {
+            cam_target -= (mouse_screen - last_mouse_screen) / cam_zoom;
+        }
+        last_mouse_screen = mouse_screen;
 
-        if is_mouse_button_down(MouseButton::Right) {
-            let color = Color::new(
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                1.0,
-            );
+        let camera = world_camera(cam_target, cam_zoom, screen_width, screen_height);
 
-            let new_ball: Ball = Ball {
-                id: balls.len(),
-                position: mouse_position,
-                velocity: vec2(
-                    rand::gen_range(-100.0, 100.0),
-                    rand::gen_range(-100.0, 100.0),
-                ),
-                color,
-                pressure: 0.0,
-                radius: ball_radius,
-            };
+        let scroll = mouse_wheel().1;
+        if scroll != 0.0 {
+            // Zoom about the cursor so the world point under it stays put
+            let anchor = camera.screen_to_world(mouse_screen);
+            let new_zoom = (cam_zoom * 1.1_f32.powf(scroll.signum())).clamp(0.1, 20.0);
 
-            balls.push(new_ball);
-            colors.push(color);
+            cam_target = anchor - (anchor - cam_target) * cam_zoom / new_zoom;
+            cam_zoom = new_zoom;
         }
 
-        for ball in balls.iter() {
-            spatial_hash.insert(ball.position, ball.id);
+        // Frame the whole world, which need not match the window's size or shape
+        if is_key_pressed(KeyCode::Home) {
+            cam_target = vec2(to_f32(world.width), to_f32(world.height)) / 2.0;
+            cam_zoom = (screen_width / to_f32(world.width))
+                .min(screen_height / to_f32(world.height))
+                .clamp(0.1, 20.0);
+        }
 
-            if display_state.display_mode == DisplayMode::Velocity {
-                if ball.velocity.length() > largest_speed {
-                    largest_speed = ball.velocity.length();
-                }
+        let camera = world_camera(cam_target, cam_zoom, screen_width, screen_height);
+        set_camera(&camera);
+
+        // All interaction happens in world space, which only matches the screen at zoom 1 with no pan
+        let mouse_position = from_vec2(camera.screen_to_world(mouse_screen));
+
+        let container = to_vec2(world.container_offset);
+        draw_rectangle_lines(
+            container.x,
+            container.y,
+            to_f32(world.width),
+            to_f32(world.height),
+            2.0,
+            DARKGREEN,
+        );
+
+        // Spawned balls are thrown along with the cursor, so dragging while spawning flings them
+        let frame_time = get_frame_time();
+        let cursor_velocity = match last_mouse_position {
+            Some(last) if frame_time > 0.0 => (mouse_position - last) / frame_time as Scalar,
+            _ => Vector2::ZERO,
+        };
+        last_mouse_position = Some(mouse_position);
+
+        if !over_panel && is_mouse_button_down(MouseButton::Right) {
+            let id = world.spawn_ball(mouse_position);
+            let spread = spawn_velocity_spread as Scalar;
+            let jitter = vector2(
+                world.rng.gen_range(-spread, spread),
+                world.rng.gen_range(-spread, spread),
+            );
+            world.balls[id].velocity = cursor_velocity * spawn_throw as Scalar + jitter;
+        }
+
+        for ball in world.balls.iter() {
+            if display_state.display_mode == DisplayMode::Velocity
+                && ball.velocity.length() > largest_speed
+            {
+                largest_speed = ball.velocity.length();
             }
 
-            if display_state.display_mode == DisplayMode::Pressure {
-                if ball.pressure > largest_pressure {
-                    largest_pressure = ball.pressure;
-                }
+            if display_state.display_mode == DisplayMode::Pressure
+                && ball.pressure > largest_pressure
+            {
+                largest_pressure = ball.pressure;
             }
-        }
 
-        for _ in 0..sim_steps {
-            for i in 0..balls.len() {
-                for &other_ball_id in spatial_hash.get_nearby_objects(balls[i].position, i).iter() {
-                    if i != other_ball_id {
-                        // Use index to get mutable references
-                        let (ball, other_ball) = if i < other_ball_id {
-                            let (left, right) = balls.split_at_mut(other_ball_id);
-                            (&mut left[i], &mut right[0])
-                        } else {
-                            let (left, right) = balls.split_at_mut(i);
-                            (&mut right[0], &mut left[other_ball_id])
-                        };
-
-                        if is_colliding(ball, other_ball) {
-                            resolve_collision(ball, other_ball, bounce_amount, max_pressure);
-                        } else {
-                            ball.pressure = 0.0;
-                            other_ball.pressure = 0.0;
-                        }
-                    }
-                }
-                resolve_boundaries(&mut balls[i], screen_width, screen_height, bounce_amount);
+            if display_state.display_mode == DisplayMode::Acceleration
+                && ball.acceleration.length() > largest_acceleration
+            {
+                largest_acceleration = ball.acceleration.length();
             }
         }
 
+        largest_speed = speed_color_scale.update(to_f32(largest_speed), frame_time) as Scalar;
+        largest_pressure =
+            pressure_color_scale.update(to_f32(largest_pressure), frame_time) as Scalar;
+        largest_acceleration =
+            acceleration_color_scale.update(to_f32(largest_acceleration), frame_time) as Scalar;
+
+        // A fixed scale keeps colours comparable across frames, auto-scaling uses the full range
+        let pressure_scale = if auto_pressure_scale {
+            largest_pressure
+        } else {
+            pressure_display_max as Scalar
+        };
+
         let delta_time = get_frame_time();
         let mut rate = delta_time;
 
@@ -290,110 +435,511 @@ async fn main() {
             rate = 0.01
         }
 
-        let mouse_pressed = is_mouse_button_down(MouseButton::Left);
-
         if is_key_pressed(KeyCode::Space) {
-            do_gravity = !do_gravity
+            world.do_gravity = !world.do_gravity
         }
 
         if is_key_pressed(KeyCode::D) {
             display_state.toggle_display_mode();
         }
 
-        for ball in balls.iter_mut() {
-            if mouse_pressed {
-                let mut force = mouse_position - ball.position;
+        if is_key_pressed(KeyCode::C) {
+            display_state.show_center_of_mass = !display_state.show_center_of_mass;
+        }
+
+        if is_key_pressed(KeyCode::Q) {
+            display_state.squash_stretch = !display_state.squash_stretch;
+        }
+
+        if is_key_pressed(KeyCode::V) {
+            display_state.show_velocity = !display_state.show_velocity;
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            profile_index = (profile_index + 1) % profile_names.len();
+            world.apply_config(&profiles[profile_names[profile_index]]);
+        }
 
-                let distance = force.length();
-                if distance < 0.1 {
-                    force /= distance;
+        if is_key_pressed(KeyCode::P) {
+            paused = !paused;
+        }
+
+        if is_key_pressed(KeyCode::T) {
+            tilting = !tilting;
+        }
+
+        if is_key_pressed(KeyCode::U) {
+            show_panel = !show_panel;
+        }
+
+        // While paused, left and right step through the last few frames instead, and right
+        // past the newest one simulates a single frame
+        if paused {
+            if is_key_pressed(KeyCode::Left) {
+                if let Some(snapshot) = rewind.back() {
+                    world.restore(snapshot);
                 }
+            } else if is_key_pressed(KeyCode::Right) {
+                if let Some(snapshot) = rewind.forward() {
+                    world.restore(snapshot);
+                } else {
+                    world.step(world.fixed_dt);
+                    rewind.push(world.snapshot());
+                }
+            }
 
-                let attraction_strength = gravity;
-                ball.velocity += force * attraction_strength * rate;
+            if display_state
+                .selected
+                .is_some_and(|id| id >= world.balls.len())
+            {
+                display_state.selected = None;
             }
+        }
 
-            if do_gravity {
-                ball.velocity.y += gravity;
+        // Arrow keys shake the container, which drifts back to where it started once let go
+        let mut push = Vec2::ZERO;
+        for (key, direction) in CONTAINER_KEYS {
+            if !paused && is_key_down(key) {
+                push += direction;
             }
+        }
+        world.container_velocity = if push == Vec2::ZERO {
+            (-world.container_offset * CONTAINER_RETURN_RATE).clamp_length_max(CONTAINER_SPEED)
+        } else {
+            from_vec2(push.normalize()) * CONTAINER_SPEED
+        };
 
-            match display_state.display_mode {
-                DisplayMode::Normal => ball.color = colors[ball.id],
-                DisplayMode::Velocity => {
-                    ball.color = get_color_from_vel(*ball, largest_speed);
-                }
-                DisplayMode::Pressure => {
-                    ball.color = get_color_from_pressure(*ball, largest_pressure);
-                }
+        if is_key_pressed(KeyCode::LeftBracket) {
+            time_scale = (time_scale / 2.0).max(0.125);
+        } else if is_key_pressed(KeyCode::RightBracket) {
+            time_scale = (time_scale * 2.0).min(8.0);
+        }
+
+        // Nudges the spatial hash cell size, to find the fastest one while watching the FPS
+        if is_key_pressed(KeyCode::Minus) {
+            world.set_cell_size(world.cell_size() / CELL_SIZE_NUDGE);
+        } else if is_key_pressed(KeyCode::Equal) {
+            world.set_cell_size(world.cell_size() * CELL_SIZE_NUDGE);
+        }
+
+        for (key, mode) in DISPLAY_MODE_KEYS {
+            if is_key_pressed(key) {
+                display_state.set_display_mode(mode);
             }
+        }
 
-            ball.velocity.x *= resistance;
-            ball.velocity.y *= resistance;
+        let picking = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
 
-            ball.velocity = ball.velocity.clamp_length_max(max_speed);
+        // Shift-click picks a ball to inspect, clicking empty space clears the selection
+        if picking && !over_panel && is_mouse_button_pressed(MouseButton::Left) {
+            display_state.selected = world.ball_at(mouse_position);
 
-            ball.position += ball.velocity * rate;
+            if let Some(id) = display_state.selected {
+                println!("{}", describe_ball(&world, id).join(", "));
+            }
+        }
 
-            draw_circle(ball.position.x, ball.position.y, ball.radius, ball.color)
+        if is_key_pressed(KeyCode::Escape) {
+            display_state.selected = None;
         }
 
-        if is_key_down(KeyCode::F) {
-            let mut to_remove: Vec<usize> = Vec::new();
+        world.attractor = (!picking && !over_panel && is_mouse_button_down(MouseButton::Left))
+            .then_some(mouse_position);
 
-            for (index, ball) in balls.iter().enumerate() {
-                let dist = ball.position.distance(mouse_position);
+        // Gravity points from the centre of the world towards the cursor, tilting it like a tray
+        world.gravity_direction = if tilting {
+            let center = vector2(world.width, world.height) / 2.0 + world.container_offset;
+            (mouse_position - center)
+                .try_normalize()
+                .unwrap_or(vector2(0.0, 1.0))
+        } else {
+            vector2(0.0, 1.0)
+        };
+
+        if is_key_pressed(KeyCode::E) {
+            if picking {
+                world.emitters.clear();
+            } else {
+                // Straight up, since y points down
+                world.emitters.push(Emitter::new(
+                    mouse_position,
+                    -consts::FRAC_PI_2,
+                    (emitter_spread_angle as Scalar).to_radians(),
+                    emitter_rate as Scalar,
+                    emitter_speed as Scalar,
+                    emitter_lifetime as Scalar,
+                ));
+            }
+        }
+
+        if is_key_pressed(KeyCode::H) {
+            if picking {
+                world.black_holes.clear();
+            } else {
+                world.black_holes.push(BlackHole::new(
+                    mouse_position,
+                    black_hole_strength as Scalar,
+                    black_hole_horizon as Scalar,
+                    black_hole_accretes,
+                ));
+            }
+        }
+
+        if is_key_pressed(KeyCode::X) {
+            if picking {
+                world.drains.clear();
+            } else {
+                world.drains.push(DrainZone::Rect {
+                    min: mouse_position - drain_size / 2.0,
+                    max: mouse_position + drain_size / 2.0,
+                });
+            }
+        }
+
+        let com_before = to_vec2(center_of_mass(&world.balls));
+        let removed_before = world.removed_count();
+
+        // Fraction of a fixed step to draw ahead of the last one, 1.0 draws the latest positions
+        let mut alpha = 1.0;
 
-                if dist < delete_dist {
-                    to_remove.push(index);
+        if !paused {
+            if fixed_timestep {
+                alpha = world.tick((rate * time_scale) as Scalar);
+            } else {
+                world.step((rate * time_scale) as Scalar);
+            }
+
+            rewind.push(world.snapshot());
+        }
+
+        if !paused {
+            if let Some(settled_at) = settling.update(&world, (rate * time_scale) as Scalar) {
+                println!("Settled after {:.2}s of simulated time", settled_at);
+            }
+        }
+
+        // Expired balls were removed and the rest reindexed, so the selected id may have moved
+        if world.removed_count() != removed_before {
+            display_state.selected = None;
+        }
+
+        let com = to_vec2(center_of_mass(&world.balls));
+        // Without gravity, the attractor or walls in play this should hold steady
+        let com_drift = if rate > 0.0 {
+            com.distance(com_before) / rate
+        } else {
+            0.0
+        };
+
+        let ball_color = |ball: &Ball| {
+            let mut color = match (&world.color_map, display_state.display_mode) {
+                (Some(color_map), _) => color_map(ball),
+                (None, DisplayMode::Normal) => ball.color,
+                (None, DisplayMode::Velocity) => get_color_from_vel(*ball, largest_speed),
+                (None, DisplayMode::Pressure) => get_color_from_pressure(*ball, pressure_scale),
+                (None, DisplayMode::Acceleration) => {
+                    get_color_from_acceleration(*ball, largest_acceleration)
                 }
+            };
+            color.a *= to_f32(ball.fade(fade_fraction as Scalar));
+            color
+        };
+
+        for ball in world.balls.iter() {
+            let position = to_vec2(ball.interpolated_position(alpha));
+            let radius = to_f32(ball.radius);
+            let velocity = to_vec2(ball.velocity);
+
+            if display_state.squash_stretch && velocity != Vec2::ZERO {
+                // Stretched along the velocity and squashed across it by the same factor, so
+                // the area drawn stays the same
+                let stretch = (1.0 + velocity.length() * STRETCH_PER_SPEED).min(MAX_STRETCH);
+                draw_ellipse(
+                    position.x,
+                    position.y,
+                    radius * stretch,
+                    radius / stretch,
+                    velocity.to_angle().to_degrees(),
+                    ball_color(ball),
+                );
+            } else {
+                draw_circle(position.x, position.y, radius, ball_color(ball))
             }
+        }
 
-            to_remove.sort_unstable_by(|a, b| b.cmp(a));
-            for idx in to_remove {
-                balls.remove(idx);
-                colors.remove(idx);
+        if display_state.show_velocity {
+            for ball in world.balls.iter() {
+                let position = ball.interpolated_position(alpha);
+                let (start, end) = (
+                    to_vec2(position),
+                    to_vec2(position + ball.velocity * VELOCITY_ARROW_TIME),
+                );
+                draw_line(start.x, start.y, end.x, end.y, 1.0 / cam_zoom, WHITE);
+            }
+        }
+
+        if is_key_pressed(KeyCode::S) {
+            svg_exports += 1;
+            let path = format!("scene_{}.svg", svg_exports);
+            let velocity_scale = display_state.show_velocity.then_some(VELOCITY_ARROW_TIME);
+            // Logged rather than unwrapped, there is no file system to write to on the web
+            match export_svg(&world, &path, ball_color, velocity_scale) {
+                Ok(()) => println!("Saved {}", path),
+                Err(error) => eprintln!("Failed to save {}: {}", path, error),
+            }
+        }
+
+        for hole in &world.black_holes {
+            let position = to_vec2(hole.position);
+            draw_circle(position.x, position.y, to_f32(hole.horizon), BLACK);
+            draw_circle_lines(
+                position.x,
+                position.y,
+                to_f32(hole.horizon),
+                2.0 / cam_zoom,
+                PURPLE,
+            );
+        }
+
+        for drain in &world.drains {
+            match *drain {
+                DrainZone::Rect { min, max } => {
+                    let (min, size) = (to_vec2(min), to_vec2(max - min));
+                    draw_rectangle_lines(min.x, min.y, size.x, size.y, 2.0 / cam_zoom, ORANGE);
+                }
+                DrainZone::Circle { center, radius } => {
+                    let center = to_vec2(center);
+                    draw_circle_lines(center.x, center.y, to_f32(radius), 2.0 / cam_zoom, ORANGE);
+                }
             }
+        }
 
-            for (idx, ball) in balls.iter_mut().enumerate() {
-                ball.id = idx;
-                colors[idx] = ball.color;
+        for emitter in &world.emitters {
+            let position = to_vec2(emitter.position);
+            draw_poly_lines(
+                position.x,
+                position.y,
+                3,
+                8.0 / cam_zoom,
+                -90.0,
+                2.0 / cam_zoom,
+                ORANGE,
+            );
+        }
+
+        if display_state.show_center_of_mass {
+            let size = 8.0 / cam_zoom;
+            draw_line(
+                com.x - size,
+                com.y,
+                com.x + size,
+                com.y,
+                2.0 / cam_zoom,
+                RED,
+            );
+            draw_line(
+                com.x,
+                com.y - size,
+                com.x,
+                com.y + size,
+                2.0 / cam_zoom,
+                RED,
+            );
+        }
+
+        if let Some(id) = display_state.selected {
+            let ball = world.balls[id];
+            let position = to_vec2(ball.position);
+            draw_circle_lines(position.x, position.y, to_f32(ball.radius), 2.0, WHITE);
+
+            for contact in world.contacts_for(id) {
+                let other_position = to_vec2(world.balls[contact.other_id].position);
+                draw_line(
+                    position.x,
+                    position.y,
+                    other_position.x,
+                    other_position.y,
+                    1.0,
+                    YELLOW,
+                );
             }
         }
 
+        if is_key_down(KeyCode::F) {
+            world.remove_balls_near(mouse_position, delete_dist as Scalar);
+
+            // Removal reindexes the balls, so the selected id may now point at a different one
+            display_state.selected = None;
+        }
+
         let fps = get_fps();
         smoothed_fps.update(fps as f32);
 
         let avg_fps = smoothed_fps.get_average();
 
+        if let Some(metrics) = metrics.as_mut() {
+            metrics
+                .record(&world, fps)
+                .expect("Failed to write metrics file");
+        }
+
+        world.run_render_hook(&RenderContext {
+            view_min: from_vec2(camera.screen_to_world(Vec2::ZERO)),
+            view_max: from_vec2(camera.screen_to_world(vec2(screen_width, screen_height))),
+            zoom: cam_zoom,
+            alpha,
+            mouse_position,
+        });
+
+        set_default_camera();
+
         draw_text(&format!("FPS: {:.2}", avg_fps), 10.0, 20.0, 30.0, WHITE);
 
         if auto_sim_steps {
-            if fps < target_fps {
-                sim_steps -= 1;
-            } else if fps > (target_fps + fps_boundary) {
-                sim_steps += 1;
-            }
-        } else {
-            if is_key_pressed(KeyCode::Up) {
-                sim_steps += 1;
-            } else if is_key_pressed(KeyCode::Down) {
-                sim_steps -= 1;
-            }
+            world.sim_steps = auto_steps.update(world.sim_steps, fps, get_frame_time());
+        } else if is_key_pressed(KeyCode::Up) {
+            world.sim_steps += 1;
+        } else if is_key_pressed(KeyCode::Down) {
+            world.sim_steps -= 1;
         }
 
-        sim_steps = sim_steps.clamp(1, 200);
+        world.sim_steps = world.sim_steps.clamp(MIN_SIM_STEPS, MAX_SIM_STEPS);
         // sim_steps = (sim_steps as f32 + 0.1 * (target_sim_steps as f32 - sim_steps as f32)) as i32;
 
         draw_text(
-            &format!("SIM STEPS: {}", sim_steps),
+            &format!("SIM STEPS: {}", world.sim_steps),
             10.0,
             50.0,
             30.0,
             WHITE,
         );
 
-        draw_text(&format!("BALLS: {}", balls.len()), 10.0, 80.0, 30.0, WHITE);
+        draw_text(
+            &format!("BALLS: {}", world.balls.len()),
+            10.0,
+            80.0,
+            30.0,
+            WHITE,
+        );
+
+        if world.island_solver {
+            draw_text(
+                &format!("ISLANDS: {}", world.island_count()),
+                10.0,
+                230.0,
+                30.0,
+                WHITE,
+            );
+        } else if world.parallel_solver {
+            draw_text(
+                &format!("COLORS: {}", world.color_count()),
+                10.0,
+                230.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        if world.adaptive_substeps {
+            draw_text(
+                &format!("SUBSTEPS: {}", world.substeps()),
+                10.0,
+                110.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        if display_state.show_center_of_mass {
+            draw_text(
+                &format!("COM DRIFT: {:.2}/s", com_drift),
+                10.0,
+                170.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        draw_text(
+            &format!("PROFILE: {}", profile_names[profile_index]),
+            10.0,
+            200.0,
+            30.0,
+            WHITE,
+        );
+
+        draw_text(
+            &format!("CELL SIZE: {:.1}", to_f32(world.cell_size())),
+            10.0,
+            260.0,
+            30.0,
+            WHITE,
+        );
+
+        if !world.drains.is_empty() {
+            draw_text(
+                &format!("DRAINED: {}", world.drained_count()),
+                10.0,
+                320.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        if world.convergence_eps > 0.0 {
+            draw_text(
+                &format!("AVG ITERATIONS: {:.1}", to_f32(world.average_iterations())),
+                10.0,
+                290.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        if paused && rewind.frames_behind() > 0 {
+            draw_text(
+                &format!("PAUSED, {} FRAMES BACK", rewind.frames_behind()),
+                10.0,
+                140.0,
+                30.0,
+                WHITE,
+            );
+        } else if paused {
+            draw_text("PAUSED", 10.0, 140.0, 30.0, WHITE);
+        } else if time_scale != 1.0 {
+            draw_text(
+                &format!("TIME SCALE: {}x", time_scale),
+                10.0,
+                140.0,
+                30.0,
+                WHITE,
+            );
+        }
+
+        if let Some(id) = display_state.selected {
+            draw_inspector(&describe_ball(&world, id));
+        }
+
+        if show_panel {
+            draw_parameter_panel(&mut world, &mut display_state);
+        }
+
+        // A custom colour map has no scale to show
+        match display_state.display_mode {
+            _ if world.color_map.is_some() => {}
+            DisplayMode::Normal => {}
+            DisplayMode::Velocity => draw_legend("SPEED", to_f32(largest_speed), velocity_gradient),
+            DisplayMode::Pressure => {
+                draw_legend("PRESSURE", to_f32(pressure_scale), pressure_gradient)
+            }
+            DisplayMode::Acceleration => draw_legend(
+                "ACCELERATION",
+                to_f32(largest_acceleration),
+                acceleration_gradient,
+            ),
+        }
 
+        frame_limiter.wait();
         next_frame().await
     }
 }
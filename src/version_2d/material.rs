@@ -0,0 +1,63 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+use crate::common::config::MaterialPair;
+use crate::scalar::Scalar;
+
+/// How a pair of materials responds to touching, in place of the world-wide settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairResponse {
+    pub bounce_amount: Scalar,
+    pub contact_damping: Scalar,
+}
+
+/// Responses for specific pairs of `Ball::material_id`s, like rubber on steel bouncing
+/// differently from rubber on rubber. Pairs without an entry use the world's settings
+#[derive(Debug, Clone, Default)]
+pub struct MaterialTable {
+    // Smallest material id first
+    pairs: HashMap<(usize, usize), PairResponse, BuildHasherDefault<DefaultHasher>>,
+}
+
+fn key(material: usize, other_material: usize) -> (usize, usize) {
+    (material.min(other_material), material.max(other_material))
+}
+
+impl MaterialTable {
+    pub fn from_config(pairs: &[MaterialPair]) -> Self {
+        let mut table = Self::default();
+
+        for pair in pairs {
+            let [material, other_material] = pair.materials;
+            table.set(
+                material,
+                other_material,
+                PairResponse {
+                    bounce_amount: pair.bounce_amount as Scalar,
+                    contact_damping: pair.contact_damping as Scalar,
+                },
+            );
+        }
+
+        table
+    }
+
+    /// Sets the response for a pair, which works either way round
+    pub fn set(&mut self, material: usize, other_material: usize, response: PairResponse) {
+        self.pairs.insert(key(material, other_material), response);
+    }
+
+    pub fn get(&self, material: usize, other_material: usize) -> Option<PairResponse> {
+        // Most worlds have no table, and this runs for every contact in every iteration
+        if self.pairs.is_empty() {
+            return None;
+        }
+
+        self.pairs.get(&key(material, other_material)).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
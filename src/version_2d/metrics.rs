@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use super::world::World2D;
+use crate::scalar::Scalar;
+
+const FLUSH_INTERVAL: usize = 60; // Rows between flushes, so a crash loses at most this many
+
+/// Appends one CSV row of aggregate world statistics per recorded frame
+pub struct MetricsWriter {
+    writer: BufWriter<File>,
+    rows: usize,
+}
+
+impl MetricsWriter {
+    /// Creates the file at `path`, replacing any existing one, and writes the header
+    pub fn create(path: &str) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(
+            writer,
+            "frame,kinetic_energy,momentum_x,momentum_y,mean_speed,max_pressure,contacts,balls,iterations,fps"
+        )?;
+
+        Ok(Self { writer, rows: 0 })
+    }
+
+    pub fn record(&mut self, world: &World2D, fps: i32) -> io::Result<()> {
+        let momentum = world.momentum();
+        let ball_count = world.balls.len();
+
+        let mut mean_speed = 0.0;
+        if ball_count > 0 {
+            mean_speed = world
+                .balls
+                .iter()
+                .map(|ball| ball.velocity.length())
+                .sum::<Scalar>()
+                / ball_count as Scalar;
+        }
+
+        let max_pressure = world
+            .balls
+            .iter()
+            .map(|ball| ball.pressure)
+            .fold(0.0, Scalar::max);
+
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.rows,
+            world.kinetic_energy(),
+            momentum.x,
+            momentum.y,
+            mean_speed,
+            max_pressure,
+            world.contact_count(),
+            ball_count,
+            world.iterations(),
+            fps
+        )?;
+
+        self.rows += 1;
+        if self.rows.is_multiple_of(FLUSH_INTERVAL) {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports when the scene has settled, meaning the total kinetic energy stayed below `threshold`
+/// for `frames` frames in a row
+pub struct SettlingMonitor {
+    pub threshold: Scalar,
+    pub frames: usize,
+    elapsed: Scalar,
+    // Simulated time the current run of calm frames started at
+    calm_since: Option<Scalar>,
+    calm_frames: usize,
+    settled_at: Option<Scalar>,
+}
+
+impl SettlingMonitor {
+    pub fn new(threshold: Scalar, frames: usize) -> Self {
+        Self {
+            threshold,
+            frames,
+            elapsed: 0.0,
+            calm_since: None,
+            calm_frames: 0,
+            settled_at: None,
+        }
+    }
+
+    /// Records a frame that advanced the world by `dt` seconds
+    ///
+    /// Returns the simulated time the scene settled at, measured from the start of the calm run,
+    /// on the one frame the run becomes long enough and `None` on every other
+    pub fn update(&mut self, world: &World2D, dt: Scalar) -> Option<Scalar> {
+        self.elapsed += dt;
+
+        if self.settled_at.is_some() {
+            return None;
+        }
+
+        if world.kinetic_energy() >= self.threshold {
+            self.calm_since = None;
+            self.calm_frames = 0;
+            return None;
+        }
+
+        // The calm run starts where the previous frame ended
+        let calm_since = *self.calm_since.get_or_insert(self.elapsed - dt);
+        self.calm_frames += 1;
+
+        if self.calm_frames >= self.frames {
+            self.settled_at = Some(calm_since);
+        }
+
+        self.settled_at
+    }
+
+    /// Simulated time the scene settled at, if it has
+    pub fn settled_at(&self) -> Option<Scalar> {
+        self.settled_at
+    }
+}
@@ -0,0 +1,50 @@
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+
+use rust_physics_engine::scalar::{to_f32, Scalar};
+use rust_physics_engine::version_2d::world::World2D;
+
+use crate::{State, DISPLAY_MODE_KEYS};
+
+const PANEL_SIZE: Vec2 = vec2(320.0, 250.0);
+const DISPLAY_MODE_NAMES: [&str; 4] = ["Normal", "Velocity", "Pressure", "Acceleration"];
+
+/// Draws the parameter panel in the top right corner, applying every change to the world
+/// as soon as it is made
+pub fn draw_parameter_panel(world: &mut World2D, display_state: &mut State) {
+    let position = vec2(screen_width() - PANEL_SIZE.x - 10.0, 10.0);
+
+    let mut gravity = to_f32(world.gravity);
+    let mut resistance = to_f32(world.resistance);
+    let mut bounce_amount = to_f32(world.bounce_amount);
+    let mut max_speed = to_f32(world.max_speed);
+    let mut sim_steps = world.sim_steps as f32;
+    let mut do_gravity = world.do_gravity;
+    let mut display_mode = DISPLAY_MODE_KEYS
+        .iter()
+        .position(|&(_, mode)| mode == display_state.display_mode)
+        .unwrap_or(0);
+
+    widgets::Window::new(hash!(), position, PANEL_SIZE)
+        .label("Parameters")
+        .ui(&mut root_ui(), |ui| {
+            ui.slider(hash!(), "gravity", 0.0..2000.0, &mut gravity);
+            ui.slider(hash!(), "resistance", 0.9..1.0, &mut resistance);
+            ui.slider(hash!(), "bounce", 0.0..1.0, &mut bounce_amount);
+            ui.slider(hash!(), "max speed", 100.0..5000.0, &mut max_speed);
+            ui.slider(hash!(), "sim steps", 1.0..200.0, &mut sim_steps);
+            ui.checkbox(hash!(), "gravity on", &mut do_gravity);
+            ui.combo_box(hash!(), "display", &DISPLAY_MODE_NAMES, &mut display_mode);
+        });
+
+    world.gravity = gravity as Scalar;
+    world.resistance = resistance as Scalar;
+    world.bounce_amount = bounce_amount as Scalar;
+    // Only when moved, since it also resets any per-ball caps
+    if max_speed != to_f32(world.max_speed) {
+        world.set_max_speed(max_speed as Scalar);
+    }
+    world.sim_steps = sim_steps.round() as i32;
+    world.do_gravity = do_gravity;
+    display_state.set_display_mode(DISPLAY_MODE_KEYS[display_mode].1);
+}
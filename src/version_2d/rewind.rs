@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use super::world::Snapshot;
+
+/// The last few frames of a world, to step backwards and forwards through while paused
+///
+/// Memory is bounded by the capacity, the oldest frame is dropped to make room for each new one
+#[derive(Debug)]
+pub struct RewindBuffer {
+    capacity: usize,
+    frames: VecDeque<Snapshot>,
+    // Index of the frame on show, the newest unless rewound
+    cursor: usize,
+}
+
+impl RewindBuffer {
+    /// Holds up to `capacity` frames, 0 to keep none
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+            cursor: 0,
+        }
+    }
+
+    /// Records a new frame after the one on show, discarding any rewound frames after it so
+    /// the simulation carries on from there
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.frames.truncate(self.cursor + 1);
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(snapshot);
+        self.cursor = self.frames.len() - 1;
+    }
+
+    /// The frame before the one on show, `None` at the oldest
+    pub fn back(&mut self) -> Option<&Snapshot> {
+        self.cursor = self.cursor.checked_sub(1)?;
+        self.frames.get(self.cursor)
+    }
+
+    /// The frame after the one on show, `None` at the newest
+    pub fn forward(&mut self) -> Option<&Snapshot> {
+        if self.cursor + 1 >= self.frames.len() {
+            return None;
+        }
+
+        self.cursor += 1;
+        self.frames.get(self.cursor)
+    }
+
+    /// How many frames the one on show is behind the newest
+    pub fn frames_behind(&self) -> usize {
+        self.frames.len().saturating_sub(self.cursor + 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
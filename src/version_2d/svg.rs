@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use macroquad::prelude::Color;
+
+use super::world::{Ball, World2D};
+use crate::scalar::{to_f32, Scalar};
+
+const STROKE_WIDTH: f32 = 2.0;
+
+/// Writes the world to `path` as an SVG, replacing any existing file
+///
+/// See [`write_svg`] for what is drawn
+pub fn export_svg(
+    world: &World2D,
+    path: &str,
+    color_of: impl Fn(&Ball) -> Color,
+    velocity_scale: Option<Scalar>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_svg(world, &mut writer, color_of, velocity_scale)?;
+    writer.flush()
+}
+
+/// Writes the balls as circles coloured by `color_of` and the container as a rectangle, in
+/// world coordinates
+///
+/// With `velocity_scale` set each ball also gets an arrow to where it will be after that
+/// many seconds
+pub fn write_svg(
+    world: &World2D,
+    writer: &mut impl Write,
+    color_of: impl Fn(&Ball) -> Color,
+    velocity_scale: Option<Scalar>,
+) -> io::Result<()> {
+    let min = world.container_offset;
+    let (width, height) = (to_f32(world.width), to_f32(world.height));
+    // Leave room for the stroke on the container's edges
+    let margin = STROKE_WIDTH;
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        to_f32(min.x) - margin,
+        to_f32(min.y) - margin,
+        width + 2.0 * margin,
+        height + 2.0 * margin,
+        width + 2.0 * margin,
+        height + 2.0 * margin,
+    )?;
+    writeln!(
+        writer,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="darkgreen" stroke-width="{}"/>"#,
+        to_f32(min.x),
+        to_f32(min.y),
+        width,
+        height,
+        STROKE_WIDTH,
+    )?;
+
+    for ball in &world.balls {
+        let color = color_of(ball);
+        writeln!(
+            writer,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="{}"/>"#,
+            to_f32(ball.position.x),
+            to_f32(ball.position.y),
+            to_f32(ball.radius),
+            svg_color(color),
+            color.a,
+        )?;
+    }
+
+    if let Some(scale) = velocity_scale {
+        writeln!(
+            writer,
+            r#"<g stroke="gray" stroke-width="{}">"#,
+            STROKE_WIDTH / 2.0
+        )?;
+        for ball in &world.balls {
+            let tip = ball.position + ball.velocity * scale;
+            writeln!(
+                writer,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}"/>"#,
+                to_f32(ball.position.x),
+                to_f32(ball.position.y),
+                to_f32(tip.x),
+                to_f32(tip.y),
+            )?;
+        }
+        writeln!(writer, "</g>")?;
+    }
+
+    writeln!(writer, "</svg>")
+}
+
+fn svg_color(color: Color) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "rgb({},{},{})",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use serde::{Deserialize, Serialize};
+
+use super::DisplayMode;
+
+/// Runtime toggles that carry over between runs, kept apart from the physics config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    pub display_mode: DisplayMode,
+    pub do_gravity: bool,
+    /// `None` to start from the configured `sim_steps`
+    pub sim_steps: Option<i32>,
+    pub time_scale: f32,
+    pub paused: bool,
+    /// World point at the centre of the screen, `None` to centre on the world bounds
+    pub cam_target: Option<[f32; 2]>,
+    pub cam_zoom: f32,
+}
+
+impl Default for UiState {
+    fn default() -> Self {
+        Self {
+            display_mode: DisplayMode::Normal,
+            do_gravity: true,
+            sim_steps: None,
+            time_scale: 1.0,
+            paused: false,
+            cam_target: None,
+            cam_zoom: 1.0,
+        }
+    }
+}
+
+/// Loads the state saved by the last run, falling back to defaults if it is missing or unreadable
+pub fn load_ui_state(path: &str) -> UiState {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return UiState::default(),
+        Err(error) => {
+            eprintln!("Failed to read {}, using defaults: {}", path, error);
+            return UiState::default();
+        }
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|error| {
+        eprintln!("Failed to parse {}, using defaults: {}", path, error);
+        UiState::default()
+    })
+}
+
+/// Saves the state for the next run, logging rather than failing since it is only a convenience
+pub fn save_ui_state(path: &str, state: &UiState) {
+    let result = serde_json::to_string_pretty(state)
+        .map_err(|error| error.to_string())
+        .and_then(|content| fs::write(path, content).map_err(|error| error.to_string()));
+
+    if let Err(error) = result {
+        eprintln!("Failed to save {}: {}", path, error);
+    }
+}
@@ -0,0 +1,2498 @@
+use std::collections::{BTreeMap, HashMap};
+
+use macroquad::prelude::*;
+
+use rayon::prelude::*;
+
+use super::builder::WorldBuilder;
+use super::coloring::color_pairs;
+use super::island::build_islands;
+use super::layout::generate_non_overlapping;
+use super::material::MaterialTable;
+use crate::common::config::{BoundaryMode, Config, GravityFalloff, InitLayout, ResolveMode, Wall};
+use crate::narrow_phase;
+use crate::rng::Rng;
+use crate::scalar::{consts, from_vec2, vector2, Scalar, Vector2};
+use crate::shape::{Circle, Shape};
+use crate::spatial_hash::SpatialHash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    /// Moved by forces and collisions
+    Dynamic,
+    /// Moved only by its externally set velocity, pushing dynamic balls as if infinitely heavy
+    Kinematic,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ball {
+    pub id: usize,
+    pub position: Vector2,
+    pub velocity: Vector2,
+    pub pressure: Scalar,
+    pub color: Color,
+    pub radius: Scalar,
+    /// Net acceleration from every force applied during the last step
+    pub acceleration: Vector2,
+    pub kind: BodyKind,
+    /// Seconds since the ball was added
+    pub age: Scalar,
+    /// Seconds the ball lives for, `None` to live forever. Balls with a lifetime are also
+    /// removed once they leave the bounds
+    pub lifetime: Option<Scalar>,
+    /// Where the ball was before the last step, for interpolating between fixed steps
+    pub previous_position: Vector2,
+    /// Multiplies the gravity on this ball, so below 1 it sinks more slowly and below 0 it floats
+    pub gravity_scale: Scalar,
+    /// Fastest this ball may move, `Scalar::INFINITY` for no limit
+    pub max_speed: Scalar,
+    /// Free for the application, e.g. to map the ball back to a game entity. The engine carries
+    /// it along, including through reindexing, but never reads it
+    pub user_data: u64,
+    /// Picks the bounce and damping against other materials from `World2D::materials`
+    pub material_id: usize,
+}
+
+impl Ball {
+    /// Opacity for drawing, ramping down to zero over the last `fade_fraction` of the lifetime
+    pub fn fade(&self, fade_fraction: Scalar) -> Scalar {
+        let Some(lifetime) = self.lifetime else {
+            return 1.0;
+        };
+
+        let remaining = (lifetime - self.age) / (lifetime * fade_fraction);
+
+        if remaining.is_finite() {
+            remaining.clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Position `alpha` of the way from the previous step to the current one
+    pub fn interpolated_position(&self, alpha: Scalar) -> Vector2 {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    /// Area of the ball, standing in for its mass when weighting positions or feeding black holes
+    pub fn mass(&self) -> Scalar {
+        self.shape().area()
+    }
+
+    pub fn shape(&self) -> Circle {
+        Circle {
+            radius: self.radius,
+        }
+    }
+
+    fn inverse_mass(&self) -> Scalar {
+        match self.kind {
+            BodyKind::Dynamic => 1.0,
+            BodyKind::Kinematic => 0.0,
+        }
+    }
+
+    /// Changes the velocity over `dt`, recording it in `acceleration`
+    ///
+    /// Every velocity change in a step goes through here so the acceleration reflects all of them
+    fn accelerate(&mut self, delta_velocity: Vector2, dt: Scalar) {
+        self.velocity += delta_velocity;
+
+        if dt > 0.0 {
+            self.acceleration += delta_velocity / dt;
+        }
+    }
+}
+
+// Below this many balls most of them having a cell to themselves is normal, not a sign of tiny cells
+const MIN_BALLS_FOR_CELL_CHECK: usize = 100;
+
+// Allowed momentum drift per step, relative to the summed speed of every ball
+const MOMENTUM_TOLERANCE: Scalar = 1e-4;
+
+// Deepest overlap left once `fill_fraction` placement stops relaxing, and the most passes it takes
+const RELAX_TOLERANCE: Scalar = 0.01;
+const MAX_RELAX_PASSES: usize = 500;
+
+// Most moves `limit_displacement` splits a step into, so a tiny or zero radius can't stall it
+const MAX_DISPLACEMENT_MOVES: usize = 64;
+
+// Positions and velocities are rounded to this before hashing, so float noise below it is ignored
+const STATE_HASH_QUANTUM: Scalar = 1e-3;
+
+// FNV-1a, written out rather than taken from std so a recorded hash survives toolchain updates
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, value: i64) -> u64 {
+    value.to_le_bytes().iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Copy of every ball and where the container was, to rewind the world to later
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    balls: Vec<Ball>,
+    container_offset: Vector2,
+    sim_time: f64,
+}
+
+/// A pair of balls the solver pushed apart during the last step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub id: usize,
+    pub other_id: usize,
+    /// Accumulated normal impulse between the pair, zero if they were only separated
+    pub impulse: Scalar,
+}
+
+impl Contact {
+    /// The other ball in this contact, if `id` is part of it
+    pub fn partner(&self, id: usize) -> Option<usize> {
+        if self.id == id {
+            Some(self.other_id)
+        } else if self.other_id == id {
+            Some(self.id)
+        } else {
+            None
+        }
+    }
+}
+
+/// Picks the colour a ball is drawn in, see `World2D::color_map`
+pub type ColorMap = Box<dyn Fn(&Ball) -> Color + Send + Sync>;
+
+/// What a render hook is told about the frame being drawn, see `World2D::on_render`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderContext {
+    /// Corners of the part of the world on screen
+    pub view_min: Vector2,
+    pub view_max: Vector2,
+    /// Pixels per world unit, so a line `1.0 / zoom` wide is one pixel wide at any zoom
+    pub zoom: f32,
+    /// Fraction of a fixed step the balls were drawn ahead of their last positions, see
+    /// `Ball::interpolated_position`
+    pub alpha: Scalar,
+    /// The cursor, in world coordinates
+    pub mouse_position: Vector2,
+}
+
+/// Draws extra annotations each frame, see `World2D::on_render`
+pub type RenderHook = Box<dyn FnMut(&World2D, &RenderContext) + Send>;
+
+/// Runs for each collision event of the ball it is registered on, see `World2D::on_collision`
+pub type CollisionCallback = Box<dyn FnMut(Contact) + Send>;
+
+fn is_colliding(ball: &Ball, otherball: &Ball) -> bool {
+    let dist = ball.position.distance(otherball.position);
+
+    dist < ball.radius + otherball.radius
+}
+
+/// Decides whether two overlapping balls collide, see `World2D::collision_filter`
+pub type CollisionFilter = Box<dyn Fn(&Ball, &Ball) -> bool + Send + Sync>;
+
+fn filter_allows(filter: &Option<CollisionFilter>, ball: &Ball, other_ball: &Ball) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|should_collide| should_collide(ball, other_ball))
+}
+
+/// Pressure a contact exerts on a ball: the magnitude of the pair's normal impulse spread over
+/// the ball's perimeter, the 2D analogue of force per unit area
+fn contact_pressure(ball: &Ball, impulse: Scalar) -> Scalar {
+    impulse / (2.0 * consts::PI * ball.radius)
+}
+
+/// Fractions of a pair's correction each ball takes, so a kinematic ball never gives way
+///
+/// Returns `None` when neither ball can move
+fn correction_shares(ball: &Ball, otherball: &Ball) -> Option<(Scalar, Scalar)> {
+    let total = ball.inverse_mass() + otherball.inverse_mass();
+
+    if total == 0.0 {
+        return None;
+    }
+
+    Some((
+        ball.inverse_mass() / total,
+        otherball.inverse_mass() / total,
+    ))
+}
+
+/// Mutably borrows two different balls at once
+fn pair_mut(balls: &mut [Ball], id: usize, other_id: usize) -> (&mut Ball, &mut Ball) {
+    if id < other_id {
+        let (left, right) = balls.split_at_mut(other_id);
+        (&mut left[id], &mut right[0])
+    } else {
+        let (left, right) = balls.split_at_mut(id);
+        (&mut right[0], &mut left[other_id])
+    }
+}
+
+/// How far two balls overlap, negative if there is a gap between them
+fn overlap_depth(ball: &Ball, other_ball: &Ball) -> Scalar {
+    ball.radius + other_ball.radius - ball.position.distance(other_ball.position)
+}
+
+/// Keeps the deepest pairs until each ball is in `max_contacts` of them, dropping the rest
+fn cap_contacts(
+    balls: &[Ball],
+    pairs: Vec<(usize, usize)>,
+    max_contacts: usize,
+) -> Vec<(usize, usize)> {
+    let mut by_depth: Vec<((usize, usize), Scalar)> = pairs
+        .into_iter()
+        .map(|(id, other_id)| ((id, other_id), overlap_depth(&balls[id], &balls[other_id])))
+        .collect();
+    // Stable, so pairs of equal depth keep the hash's order and the result stays deterministic
+    by_depth.sort_by(|(_, depth), (_, other_depth)| other_depth.total_cmp(depth));
+
+    let mut counts = vec![0; balls.len()];
+    by_depth
+        .into_iter()
+        .filter_map(|((id, other_id), _)| {
+            if counts[id] >= max_contacts || counts[other_id] >= max_contacts {
+                return None;
+            }
+
+            counts[id] += 1;
+            counts[other_id] += 1;
+            Some((id, other_id))
+        })
+        .collect()
+}
+
+fn pair_key(id: usize, other_id: usize) -> (usize, usize) {
+    if id < other_id {
+        (id, other_id)
+    } else {
+        (other_id, id)
+    }
+}
+
+/// Re-applies last frame's accumulated impulse so the solver starts near the resting solution
+fn warm_start(ball: &mut Ball, otherball: &mut Ball, impulse: Scalar, dt: Scalar) {
+    let Some((share, other_share)) = correction_shares(ball, otherball) else {
+        return;
+    };
+
+    let normal = (otherball.position - ball.position).normalize_or_zero();
+
+    ball.accelerate(-normal * impulse * 2.0 * share, dt);
+    otherball.accelerate(normal * impulse * 2.0 * other_share, dt);
+
+    ball.pressure += contact_pressure(ball, impulse);
+    otherball.pressure += contact_pressure(otherball, impulse);
+}
+
+// `bounce_amount` that cancels a pair's approach without bouncing them apart again
+const INELASTIC_BOUNCE: Scalar = 0.5;
+
+/// How fast a pair is moving apart along the line between their centres, zero if approaching
+fn separating_speed(ball: &Ball, otherball: &Ball) -> Scalar {
+    let normal = (otherball.position - ball.position).normalize_or_zero();
+    (otherball.velocity - ball.velocity).dot(normal).max(0.0)
+}
+
+/// The part of last step's impulse to warm start a pair with
+///
+/// With `clamp_contact_impulse` the carried over impulse is only what stopped the pair, see
+/// `stopping_impulse`, less however fast it is already separating
+fn warm_start_impulse(
+    ball: &Ball,
+    otherball: &Ball,
+    impulse: Scalar,
+    settings: ContactSettings<'_>,
+) -> Scalar {
+    if !settings.clamp_contact_impulse {
+        return impulse;
+    }
+
+    // The impulse changes their relative speed by twice itself
+    (impulse - separating_speed(ball, otherball) * 0.5).max(0.0)
+}
+
+/// The part of a pair's accumulated impulse that stopped it approaching, rather than bounced it
+///
+/// The bounce is worked out again from each step's approach, so carrying it over too would
+/// push apart pairs that have already bounced. Only what the solver added on top of the warm
+/// start answered a new approach, so the warm started part is kept whole and resting contacts
+/// hold their load from step to step
+fn stopping_impulse(
+    ball: &Ball,
+    otherball: &Ball,
+    warm_impulse: Scalar,
+    impulse: Scalar,
+    settings: ContactSettings<'_>,
+) -> Scalar {
+    let added = impulse - warm_impulse;
+    if added <= 0.0 {
+        return impulse;
+    }
+
+    let bounce_amount = settings
+        .materials
+        .get(ball.material_id, otherball.material_id)
+        .map_or(settings.bounce_amount, |response| response.bounce_amount);
+
+    // The added impulse was `bounce_amount` times the approach it answered, and below the
+    // threshold that approach was only stopped
+    if bounce_amount <= INELASTIC_BOUNCE
+        || added / bounce_amount < settings.restitution_velocity_threshold
+    {
+        impulse
+    } else {
+        warm_impulse + added * INELASTIC_BOUNCE / bounce_amount
+    }
+}
+
+/// World settings that shape how every colliding pair is resolved
+#[derive(Debug, Clone, Copy)]
+struct ContactSettings<'a> {
+    bounce_amount: Scalar,
+    restitution_velocity_threshold: Scalar,
+    max_correction: Scalar,
+    clamp_contact_impulse: bool,
+    contact_damping: Scalar,
+    resolve_mode: ResolveMode,
+    materials: &'a MaterialTable,
+}
+
+/// Separates and bounces an overlapping pair, returning how far it pushed them apart
+fn resolve_collision(
+    ball: &mut Ball,
+    otherball: &mut Ball,
+    settings: ContactSettings<'_>,
+    accumulated_impulse: &mut Scalar,
+    dt: Scalar,
+) -> Scalar {
+    let Some(contact) = narrow_phase::collide_circles(
+        ball.position,
+        ball.radius,
+        otherball.position,
+        otherball.radius,
+    ) else {
+        return 0.0;
+    };
+
+    if contact.depth < 0.001 {
+        return 0.0;
+    }
+
+    let Some((share, other_share)) = correction_shares(ball, otherball) else {
+        return 0.0;
+    };
+
+    let (pdiff, overlap) = (contact.normal, contact.depth);
+
+    // Deep overlaps are worked off over several iterations rather than flinging the pair apart
+    let correction = overlap.min(settings.max_correction);
+
+    ball.position -= pdiff * correction * share;
+    otherball.position += pdiff * correction * other_share;
+
+    if settings.resolve_mode == ResolveMode::PositionOnly {
+        return correction;
+    }
+
+    let bounce_amount = settings
+        .materials
+        .get(ball.material_id, otherball.material_id)
+        .map_or(settings.bounce_amount, |response| response.bounce_amount);
+
+    let relative_velocity = otherball.velocity - ball.velocity;
+    let dot_product = relative_velocity.dot(pdiff);
+
+    // Below the threshold the pair only stops approaching rather than bouncing, so resting
+    // contacts settle instead of buzzing. Sticky pairs never bounce, the bond made from the
+    // contact holds them together instead
+    let approach_speed = -dot_product;
+    let bounce_amount = if settings.resolve_mode == ResolveMode::Sticky
+        || (approach_speed > 0.0 && approach_speed < settings.restitution_velocity_threshold)
+    {
+        bounce_amount.min(INELASTIC_BOUNCE)
+    } else {
+        bounce_amount
+    };
+
+    // Clamp the accumulated impulse rather than each increment, so a later iteration can
+    // take back an overshoot but the pair is never pulled together
+    let old_impulse = *accumulated_impulse;
+    *accumulated_impulse = (old_impulse - dot_product * bounce_amount).max(0.0);
+
+    if settings.clamp_contact_impulse {
+        // The accumulated impulse has changed the pair's relative speed by twice itself, so
+        // this is how fast they would be approaching without it. An impulse equal to that
+        // already bounces them apart as fast as they came together
+        let unaided_approach = 2.0 * old_impulse - dot_product;
+        *accumulated_impulse = accumulated_impulse.min(unaided_approach.max(0.0));
+    }
+
+    let force = old_impulse - *accumulated_impulse;
+
+    // Summed over every iteration and contact this adds up to each pair's final accumulated impulse
+    ball.pressure += contact_pressure(ball, -force);
+    otherball.pressure += contact_pressure(otherball, -force);
+
+    // Equal shares give each ball `force`, a kinematic partner leaves the full change to the other
+    ball.accelerate(pdiff * force * 2.0 * share, dt);
+    otherball.accelerate(-pdiff * force * 2.0 * other_share, dt);
+
+    correction
+}
+
+/// Removes `contact_damping` of a touching pair's relative velocity, spread over `substeps`
+///
+/// Called once per pair per substep after the solver iterations, so the loss over a step is
+/// the same however many iterations and substeps it takes
+fn damp_contact(
+    ball: &mut Ball,
+    otherball: &mut Ball,
+    settings: ContactSettings<'_>,
+    substeps: usize,
+    dt: Scalar,
+) {
+    let contact_damping = settings
+        .materials
+        .get(ball.material_id, otherball.material_id)
+        .map_or(settings.contact_damping, |response| {
+            response.contact_damping
+        });
+
+    if contact_damping <= 0.0 {
+        return;
+    }
+
+    let Some((share, other_share)) = correction_shares(ball, otherball) else {
+        return;
+    };
+
+    // Substeps that each keep (1 - d)^(1/n) of the velocity keep 1 - d of it between them
+    let contact_damping = 1.0 - (1.0 - contact_damping).powf((substeps as Scalar).recip());
+
+    // Contact losses only touch the pair's relative motion, so momentum is unaffected
+    let damping = (otherball.velocity - ball.velocity) * contact_damping;
+    ball.accelerate(damping * share, dt);
+    otherball.accelerate(-damping * other_share, dt);
+}
+
+/// Holds two balls at a fixed distance from each other, like a massless rod between their centres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bond {
+    pub id: usize,
+    pub other_id: usize,
+    pub rest_length: Scalar,
+}
+
+/// Moves a bonded pair back to its rest length and cancels their relative velocity along the
+/// bond, leaving them free to spin around each other
+fn resolve_bond(ball: &mut Ball, otherball: &mut Ball, rest_length: Scalar, dt: Scalar) {
+    let Some((share, other_share)) = correction_shares(ball, otherball) else {
+        return;
+    };
+
+    let pdiff = otherball.position - ball.position;
+    let dist = pdiff.length();
+
+    if dist == 0.0 {
+        return;
+    }
+
+    let normal = pdiff / dist;
+    let stretch = dist - rest_length;
+
+    ball.position += normal * stretch * share;
+    otherball.position -= normal * stretch * other_share;
+
+    let separating_speed = (otherball.velocity - ball.velocity).dot(normal);
+    ball.accelerate(normal * separating_speed * share, dt);
+    otherball.accelerate(-normal * separating_speed * other_share, dt);
+}
+
+/// Pulls a nearby but separate pair together, more weakly the further apart they are
+fn apply_cohesion(
+    ball: &mut Ball,
+    otherball: &mut Ball,
+    strength: Scalar,
+    interaction_radius: Scalar,
+    dt: Scalar,
+) {
+    let pdiff = otherball.position - ball.position;
+    let dist = pdiff.length();
+
+    if dist <= ball.radius + otherball.radius || dist >= interaction_radius {
+        return;
+    }
+
+    let Some((share, other_share)) = correction_shares(ball, otherball) else {
+        return;
+    };
+
+    let force = pdiff / dist * strength * (1.0 - dist / interaction_radius);
+
+    // Shares sum to one, so doubling them gives each of an equal pair the full force
+    ball.accelerate(force * 2.0 * share * dt, dt);
+    otherball.accelerate(-force * 2.0 * other_share * dt, dt);
+}
+
+fn gravity_at(
+    falloff: GravityFalloff,
+    gravity: Scalar,
+    direction: Vector2,
+    center: Vector2,
+    radius: Scalar,
+    size: Vector2,
+    position: Vector2,
+) -> Vector2 {
+    match falloff {
+        GravityFalloff::Constant => direction * gravity,
+        GravityFalloff::Linear => {
+            // The floor is the side of the world `direction` points at, and altitude is
+            // measured from it back along `-direction`, across the world's extent that way
+            let floor = size.x * direction.x.max(0.0) + size.y * direction.y.max(0.0);
+            let extent = size.x * direction.x.abs() + size.y * direction.y.abs();
+            if extent <= 0.0 {
+                return Vector2::ZERO;
+            }
+
+            let altitude = (floor - position.dot(direction)).clamp(0.0, extent);
+            direction * gravity * (1.0 - altitude / extent)
+        }
+        GravityFalloff::InverseSquare => {
+            let offset = center - position;
+            let distance = offset.length().max(radius);
+
+            offset.normalize_or_zero() * gravity * (radius / distance).powi(2)
+        }
+    }
+}
+
+/// Velocity change that reflects `speed` into a wall, keeping the wall's share of it
+fn wall_bounce(speed: Scalar, wall: &Wall, restitution_threshold: Scalar) -> Scalar {
+    let restitution = if speed.abs() < restitution_threshold {
+        0.0
+    } else {
+        wall.restitution as Scalar
+    };
+
+    // Going from v to -v * restitution is a change of -v * (1 + restitution)
+    -(1.0 + restitution) * speed
+}
+
+/// Where the walls are and how fast they are moving
+#[derive(Debug, Clone, Copy)]
+struct Container {
+    min: Vector2,
+    max: Vector2,
+    velocity: Vector2,
+}
+
+/// Keeps a ball inside the container, bouncing it off the walls in the walls' own frame
+///
+/// A moving wall hands its velocity on, so a rising floor lifts the balls resting on it
+fn resolve_boundaries(
+    ball: &mut Ball,
+    container: Container,
+    walls: &[Wall; 4],
+    restitution_threshold: Scalar,
+    dt: Scalar,
+) {
+    let [left, right, top, bottom] = walls;
+    let Container { min, max, velocity } = container;
+    let relative = ball.velocity - velocity;
+
+    if ball.position.x - ball.radius < min.x && left.blocks(relative.x <= 0.0) {
+        ball.position.x = min.x + ball.radius;
+        if relative.x < 0.0 {
+            ball.accelerate(
+                vector2(wall_bounce(relative.x, left, restitution_threshold), 0.0),
+                dt,
+            );
+        }
+    } else if ball.position.x + ball.radius > max.x && right.blocks(relative.x >= 0.0) {
+        ball.position.x = max.x - ball.radius;
+        if relative.x > 0.0 {
+            ball.accelerate(
+                vector2(wall_bounce(relative.x, right, restitution_threshold), 0.0),
+                dt,
+            );
+        }
+    }
+
+    if ball.position.y - ball.radius < min.y && top.blocks(relative.y <= 0.0) {
+        ball.position.y = min.y + ball.radius;
+        if relative.y < 0.0 {
+            ball.accelerate(
+                vector2(0.0, wall_bounce(relative.y, top, restitution_threshold)),
+                dt,
+            );
+        }
+    } else if ball.position.y + ball.radius > max.y && bottom.blocks(relative.y >= 0.0) {
+        ball.position.y = max.y - ball.radius;
+        if relative.y > 0.0 {
+            ball.accelerate(
+                vector2(0.0, wall_bounce(relative.y, bottom, restitution_threshold)),
+                dt,
+            );
+        }
+    }
+}
+
+/// Pushes a ball back inside the walls with a damped spring proportional to how far it has sunk in
+fn resolve_soft_boundaries(
+    ball: &mut Ball,
+    container: Container,
+    stiffness: Scalar,
+    damping: Scalar,
+    dt: Scalar,
+) {
+    let mut force = Vector2::ZERO;
+    let relative = ball.velocity - container.velocity;
+
+    let left = container.min.x + ball.radius - ball.position.x;
+    let right = ball.position.x + ball.radius - container.max.x;
+    let top = container.min.y + ball.radius - ball.position.y;
+    let bottom = ball.position.y + ball.radius - container.max.y;
+
+    if left > 0.0 {
+        force.x += stiffness * left - damping * relative.x;
+    } else if right > 0.0 {
+        force.x -= stiffness * right + damping * relative.x;
+    }
+
+    if top > 0.0 {
+        force.y += stiffness * top - damping * relative.y;
+    } else if bottom > 0.0 {
+        force.y -= stiffness * bottom + damping * relative.y;
+    }
+
+    ball.accelerate(force * dt, dt);
+}
+
+fn random_color(rng: &mut Rng) -> Color {
+    Color::new(rng.next_f32(), rng.next_f32(), rng.next_f32(), 1.0)
+}
+
+/// `scale`, or a random value up to `spread` either side of it
+fn random_gravity_scale(scale: Scalar, spread: Scalar, rng: &mut Rng) -> Scalar {
+    if spread > 0.0 {
+        rng.gen_range(scale - spread, scale + spread)
+    } else {
+        scale
+    }
+}
+
+fn random_velocity(rng: &mut Rng) -> Vector2 {
+    vector2(rng.gen_range(-100.0, 100.0), rng.gen_range(-100.0, 100.0))
+}
+
+/// Number of balls a new world starts with, enough to cover `fill_fraction` of its area if set
+fn initial_ball_count(config: &Config) -> usize {
+    let Some(fill_fraction) = config.fill_fraction else {
+        return config.ball_count_2d;
+    };
+
+    let radius = config.ball_radius as Scalar;
+    let area = config.width as Scalar * config.height as Scalar;
+
+    (fill_fraction as Scalar * area / (consts::PI * radius * radius)).round() as usize
+}
+
+/// Where ball `index` of `ball_count` starts for the configured `init_layout`
+fn initial_position(config: &Config, ball_count: usize, index: usize, rng: &mut Rng) -> Vector2 {
+    let radius = config.ball_radius as Scalar;
+    let spacing = 2.0 * radius + config.layout_gap as Scalar;
+    let per_row = ((ball_count as Scalar).sqrt().ceil() as usize).max(1);
+    let (row, col) = (index / per_row, index % per_row);
+
+    match config.init_layout {
+        InitLayout::Random => vector2(
+            rng.gen_range(radius, config.width as Scalar - radius),
+            rng.gen_range(radius, config.height as Scalar - radius),
+        ),
+        InitLayout::Grid => vector2(
+            radius + col as Scalar * spacing,
+            radius + row as Scalar * spacing,
+        ),
+        InitLayout::Spaced => unreachable!("spaced layouts are generated all at once"),
+        InitLayout::HexPack => {
+            // Odd rows shift by half a spacing so each ball nests in the gap between two below it
+            let offset = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+
+            vector2(
+                radius + offset + col as Scalar * spacing,
+                radius + row as Scalar * spacing * (3.0 as Scalar).sqrt() / 2.0,
+            )
+        }
+    }
+}
+
+/// Spawns a stream of short-lived balls from a point
+#[derive(Debug, Clone, Copy)]
+pub struct Emitter {
+    pub position: Vector2,
+    /// Direction of the stream in radians, clockwise from +x since y points down
+    pub angle: Scalar,
+    /// Full width of the cone balls are fired into, in radians
+    pub spread_angle: Scalar,
+    /// Balls per second
+    pub rate: Scalar,
+    pub speed: Scalar,
+    /// Seconds each emitted ball lives for
+    pub lifetime: Scalar,
+    // Fraction of a ball carried over from earlier steps, so low rates still emit
+    pending: Scalar,
+}
+
+impl Emitter {
+    pub fn new(
+        position: Vector2,
+        angle: Scalar,
+        spread_angle: Scalar,
+        rate: Scalar,
+        speed: Scalar,
+        lifetime: Scalar,
+    ) -> Self {
+        Self {
+            position,
+            angle,
+            spread_angle,
+            rate,
+            speed,
+            lifetime,
+            pending: 0.0,
+        }
+    }
+
+    /// How many balls are due after `dt` more seconds
+    fn due(&mut self, dt: Scalar) -> usize {
+        self.pending += self.rate * dt;
+
+        let count = self.pending.floor();
+        self.pending -= count;
+
+        count as usize
+    }
+
+    fn emit_velocity(&self, rng: &mut Rng) -> Vector2 {
+        let half_spread = self.spread_angle / 2.0;
+        let angle = self.angle + rng.gen_range(-half_spread, half_spread);
+
+        Vector2::from_angle(angle) * self.speed
+    }
+}
+
+/// Pulls balls in with inverse-square attraction and removes any that cross its event horizon
+#[derive(Debug, Clone, Copy)]
+pub struct BlackHole {
+    pub position: Vector2,
+    /// Acceleration at the horizon in units/s², falling off with the square of the distance
+    /// beyond it
+    pub strength: Scalar,
+    /// Radius within which balls are consumed
+    pub horizon: Scalar,
+    /// Grow `strength` by the mass of every ball consumed
+    pub accretes: bool,
+}
+
+impl BlackHole {
+    pub fn new(position: Vector2, strength: Scalar, horizon: Scalar, accretes: bool) -> Self {
+        Self {
+            position,
+            strength,
+            horizon,
+            accretes,
+        }
+    }
+
+    /// Acceleration towards the hole at `position`, capped at its horizon strength so balls
+    /// closing in on the centre aren't flung through it
+    fn pull(&self, position: Vector2) -> Vector2 {
+        let offset = self.position - position;
+        let distance = offset.length().max(self.horizon);
+
+        offset.normalize_or_zero() * self.strength * (self.horizon / distance).powi(2)
+    }
+
+    fn consumes(&self, ball: &Ball) -> bool {
+        ball.kind == BodyKind::Dynamic && ball.position.distance(self.position) < self.horizon
+    }
+}
+
+/// Region that removes every dynamic ball whose centre enters it, e.g. the outlet of a hopper
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrainZone {
+    Rect { min: Vector2, max: Vector2 },
+    Circle { center: Vector2, radius: Scalar },
+}
+
+impl DrainZone {
+    pub fn contains(&self, point: Vector2) -> bool {
+        match *self {
+            DrainZone::Rect { min, max } => {
+                point.x >= min.x && point.x <= max.x && point.y >= min.y && point.y <= max.y
+            }
+            DrainZone::Circle { center, radius } => point.distance(center) < radius,
+        }
+    }
+
+    fn drains(&self, ball: &Ball) -> bool {
+        ball.kind == BodyKind::Dynamic && self.contains(ball.position)
+    }
+}
+
+/// Centre of mass of the dynamic balls, weighting each by its area
+///
+/// Kinematic balls are left out, since they move regardless of what they hit
+pub fn center_of_mass(balls: &[Ball]) -> Vector2 {
+    let (weighted, total) = balls
+        .iter()
+        .filter(|ball| ball.kind == BodyKind::Dynamic)
+        .fold((Vector2::ZERO, 0.0), |(weighted, total), ball| {
+            (weighted + ball.position * ball.mass(), total + ball.mass())
+        });
+
+    if total > 0.0 {
+        weighted / total
+    } else {
+        Vector2::ZERO
+    }
+}
+
+/// The 2D ball simulation, without any windowing, input or rendering
+pub struct World2D {
+    pub balls: Vec<Ball>,
+    /// Size of the world's bounds, independent of any window it is drawn in
+    pub width: Scalar,
+    pub height: Scalar,
+    pub ball_radius: Scalar,
+    /// Downward acceleration in units/s²
+    pub gravity: Scalar,
+    /// Unit vector constant and linear gravity pull along, straight down unless tilted
+    pub gravity_direction: Vector2,
+    pub attraction_strength: Scalar,
+    pub gravity_falloff: GravityFalloff,
+    pub gravity_center: Vector2,
+    pub gravity_radius: Scalar,
+    /// Gravity swings this far either side of `gravity`, in units/s², `gravity_pulse_frequency`
+    /// times per second of simulated time
+    pub gravity_pulse_amplitude: Scalar,
+    pub gravity_pulse_frequency: Scalar,
+    /// `gravity_scale` given to new balls, randomised by up to `gravity_scale_spread` either way
+    pub gravity_scale: Scalar,
+    pub gravity_scale_spread: Scalar,
+    pub resistance: Scalar,
+    pub bounce_amount: Scalar,
+    /// Approach speed below which balls and walls stop a ball dead instead of bouncing it
+    pub restitution_velocity_threshold: Scalar,
+    /// Speed cap given to new balls, see `set_max_speed` to change it for existing ones too
+    pub max_speed: Scalar,
+    pub max_pressure: Scalar,
+    /// Furthest a colliding pair is pushed apart by a single resolution
+    pub max_correction: Scalar,
+    /// Never let a contact push its pair apart faster than they were approaching
+    pub clamp_contact_impulse: bool,
+    /// Fraction of a touching pair's relative velocity removed each step, however many solver
+    /// iterations and substeps the step takes
+    pub contact_damping: Scalar,
+    pub resolve_mode: ResolveMode,
+    /// Bounce and damping for specific pairs of `Ball::material_id`s, overriding
+    /// `bounce_amount` and `contact_damping`
+    pub materials: MaterialTable,
+    /// Solver iterations per step, or the most of them with `convergence_eps` set
+    pub sim_steps: i32,
+    /// Largest correction in an iteration below which the rest of the step's iterations are skipped
+    pub convergence_eps: Scalar,
+    /// Keep `peak_penetration` up to date, which costs a pass over the contacts every step
+    pub track_penetration: bool,
+    pub walls: [Wall; 4],
+    /// Top left corner of the walls, which move with `container_velocity` to shake the balls
+    pub container_offset: Vector2,
+    /// Velocity of the walls, handed on to the balls that hit them
+    pub container_velocity: Vector2,
+    pub boundary_mode: BoundaryMode,
+    pub wall_stiffness: Scalar,
+    pub wall_damping: Scalar,
+    pub limit_displacement: bool,
+    pub cohesion_strength: Scalar,
+    /// Centre distance within which separate balls attract. Call `set_cell_size` after raising
+    /// it, so the hash cells grow to match
+    pub interaction_radius: Scalar,
+    /// Solve each group of touching balls separately instead of all pairs together
+    pub island_solver: bool,
+    /// Resolve contacts in parallel, in colour classes that share no balls. Ignored when
+    /// `island_solver` is on
+    pub parallel_solver: bool,
+    /// Shuffle the contacts with `rng` before each solver iteration, so no contact is always
+    /// resolved first. Ignored when `parallel_solver` is on
+    pub randomize_contact_order: bool,
+    /// Caps the contacts resolved per ball each step, keeping the deepest, to bound the cost of
+    /// dense pile-ups at some cost in accuracy
+    pub max_contacts_per_body: Option<usize>,
+    /// Smallest contact impulse reported as a collision event
+    pub min_event_impulse: Scalar,
+    /// Seconds after a collision event before either ball can be in another
+    pub event_cooldown: Scalar,
+    pub adaptive_substeps: bool,
+    /// Occupied hash cells per ball above which the cell size is reported as too small
+    pub cell_ratio_threshold: Scalar,
+    /// Double the cell size each step the threshold is exceeded, instead of only warning
+    pub auto_cell_size: bool,
+    pub substep_fraction: Scalar,
+    pub max_substeps: usize,
+    /// Emitters stop spawning while there are at least this many balls
+    pub max_balls: usize,
+    /// Length of each step taken by `tick`
+    pub fixed_dt: Scalar,
+    pub max_steps_per_tick: usize,
+    /// Seconds balls added by `spawn_ball` live for, `None` to keep them forever
+    pub spawn_lifetime: Option<Scalar>,
+    pub emitters: Vec<Emitter>,
+    pub black_holes: Vec<BlackHole>,
+    pub drains: Vec<DrainZone>,
+    pub do_gravity: bool,
+    /// Point every ball is pulled towards during the next step, e.g. while the mouse is held
+    pub attractor: Option<Vector2>,
+    /// Called for every overlapping pair before it is resolved, letting the pair pass through
+    /// each other when it returns false. Every pair collides if unset
+    pub collision_filter: Option<CollisionFilter>,
+    /// Colours every ball when set, in place of whichever display mode is chosen, e.g. to tell
+    /// balls apart by their `user_data`. Only the renderer reads it
+    pub color_map: Option<ColorMap>,
+    /// Called each frame once the balls are drawn, with the world camera still set so macroquad's
+    /// draw functions take world coordinates. Only the renderer calls it
+    pub on_render: Option<RenderHook>,
+    /// Source of every random choice the world makes, seeded from `seed` in the config
+    pub rng: Rng,
+    spatial_hash: SpatialHash<usize>,
+    // Ordered by pair so warm starting applies impulses in the same order on every toolchain
+    contact_impulses: BTreeMap<(usize, usize), Scalar>,
+    contacts: Vec<Contact>,
+    // Every contact twice, once from each ball's side, grouped by ball for `contacts_for`
+    ball_contacts: Vec<Contact>,
+    // Where each ball's group starts in `ball_contacts`, followed by the end of the last group
+    ball_contact_starts: Vec<usize>,
+    // Rest length of each bonded pair, smallest id first
+    bonds: BTreeMap<(usize, usize), Scalar>,
+    collision_events: Vec<Contact>,
+    // Keyed by ball id, remapped along with the balls
+    collision_callbacks: HashMap<usize, CollisionCallback>,
+    // Seconds until each ball, by id, can be in another collision event
+    event_cooldowns: Vec<Scalar>,
+    substeps: usize,
+    // Solver iterations of the last step, over every substep
+    iterations: usize,
+    total_iterations: usize,
+    step_count: usize,
+    // Deepest overlap left after any step while `track_penetration` was on
+    peak_penetration: Scalar,
+    cell_warning_logged: bool,
+    removed_count: usize,
+    drained_count: usize,
+    island_count: usize,
+    color_count: usize,
+    accumulator: Scalar,
+    // Seconds of simulated time stepped so far, in f64 so it doesn't lose precision over long runs
+    sim_time: f64,
+}
+
+impl World2D {
+    /// Creates a world filled with `ball_count_2d` balls placed according to `init_layout`
+    ///
+    /// With `fill_fraction` set the ball count is worked out from it instead, and the balls are
+    /// pushed apart until they no longer overlap
+    pub fn new(config: &Config) -> Self {
+        let ball_radius = config.ball_radius as Scalar;
+        let width = config.width as Scalar;
+        let height = config.height as Scalar;
+
+        // Cells must span the interaction radius for cohesive pairs to show up as candidates
+        let mut cell_size = (ball_radius * 2.0) + 2.0;
+        if config.cohesion_strength as Scalar > 0.0 {
+            cell_size = cell_size.max(config.interaction_radius as Scalar);
+        }
+
+        let mut rng = Rng::new(config.seed);
+
+        let ball_count = initial_ball_count(config);
+        // Each spaced position depends on all the others, so they are drawn before anything else
+        let spaced = (config.init_layout == InitLayout::Spaced).then(|| {
+            generate_non_overlapping(
+                ball_count,
+                (Vector2::ZERO, vector2(width, height)),
+                ball_radius + config.layout_gap as Scalar / 2.0,
+                &mut rng,
+            )
+        });
+        let ball_count = spaced.as_ref().map_or(ball_count, Vec::len);
+        let balls = (0..ball_count)
+            .map(|id| {
+                let position = match &spaced {
+                    Some(positions) => positions[id],
+                    None => initial_position(config, ball_count, id, &mut rng),
+                };
+
+                Ball {
+                    id,
+                    position,
+                    velocity: random_velocity(&mut rng),
+                    pressure: 0.0,
+                    color: random_color(&mut rng),
+                    radius: ball_radius,
+                    acceleration: Vector2::ZERO,
+                    kind: BodyKind::Dynamic,
+                    age: 0.0,
+                    lifetime: None,
+                    previous_position: position,
+                    gravity_scale: random_gravity_scale(
+                        config.gravity_scale as Scalar,
+                        config.gravity_scale_spread as Scalar,
+                        &mut rng,
+                    ),
+                    max_speed: config.max_speed as Scalar,
+                    user_data: 0,
+                    material_id: 0,
+                }
+            })
+            .collect();
+
+        let mut world = Self {
+            balls,
+            width,
+            height,
+            ball_radius,
+            gravity: config.gravity as Scalar,
+            gravity_direction: vector2(0.0, 1.0),
+            attraction_strength: config.attraction_strength as Scalar,
+            gravity_falloff: config.gravity_falloff,
+            gravity_center: from_vec2(Vec2::from(config.gravity_center)),
+            gravity_radius: config.gravity_radius as Scalar,
+            gravity_pulse_amplitude: config.gravity_pulse_amplitude as Scalar,
+            gravity_pulse_frequency: config.gravity_pulse_frequency as Scalar,
+            gravity_scale: config.gravity_scale as Scalar,
+            gravity_scale_spread: config.gravity_scale_spread as Scalar,
+            resistance: config.resistance as Scalar,
+            bounce_amount: config.bounce_amount as Scalar,
+            restitution_velocity_threshold: config.restitution_velocity_threshold as Scalar,
+            max_speed: config.max_speed as Scalar,
+            max_pressure: config.max_pressure as Scalar,
+            max_correction: config.max_correction as Scalar,
+            clamp_contact_impulse: config.clamp_contact_impulse,
+            contact_damping: config.contact_damping as Scalar,
+            resolve_mode: config.resolve_mode,
+            materials: MaterialTable::from_config(&config.material_pairs),
+            sim_steps: config.sim_steps,
+            convergence_eps: config.convergence_eps as Scalar,
+            track_penetration: config.track_penetration,
+            walls: config.walls_2d,
+            container_offset: Vector2::ZERO,
+            container_velocity: Vector2::ZERO,
+            boundary_mode: config.boundary_mode,
+            wall_stiffness: config.wall_stiffness as Scalar,
+            wall_damping: config.wall_damping as Scalar,
+            limit_displacement: config.limit_displacement,
+            cohesion_strength: config.cohesion_strength as Scalar,
+            interaction_radius: config.interaction_radius as Scalar,
+            island_solver: config.island_solver,
+            parallel_solver: config.parallel_solver,
+            randomize_contact_order: config.randomize_contact_order,
+            max_contacts_per_body: config.max_contacts_per_body,
+            min_event_impulse: config.min_event_impulse as Scalar,
+            event_cooldown: config.event_cooldown as Scalar,
+            adaptive_substeps: config.adaptive_substeps,
+            cell_ratio_threshold: config.cell_ratio_threshold as Scalar,
+            auto_cell_size: config.auto_cell_size,
+            substep_fraction: config.substep_fraction as Scalar,
+            max_substeps: config.max_substeps,
+            max_balls: config.max_balls,
+            fixed_dt: config.fixed_dt as Scalar,
+            max_steps_per_tick: config.max_steps_per_tick,
+            spawn_lifetime: config.spawn_lifetime.map(|lifetime| lifetime as Scalar),
+            emitters: Vec::new(),
+            black_holes: Vec::new(),
+            drains: Vec::new(),
+            do_gravity: true,
+            attractor: None,
+            collision_filter: None,
+            color_map: None,
+            on_render: None,
+            rng,
+            spatial_hash: SpatialHash::new(cell_size),
+            contact_impulses: BTreeMap::new(),
+            contacts: Vec::new(),
+            ball_contacts: Vec::new(),
+            ball_contact_starts: Vec::new(),
+            bonds: BTreeMap::new(),
+            collision_events: Vec::new(),
+            collision_callbacks: HashMap::new(),
+            event_cooldowns: Vec::new(),
+            substeps: 1,
+            iterations: 0,
+            total_iterations: 0,
+            step_count: 0,
+            peak_penetration: 0.0,
+            cell_warning_logged: false,
+            removed_count: 0,
+            drained_count: 0,
+            island_count: 0,
+            color_count: 0,
+            accumulator: 0.0,
+            sim_time: 0.0,
+        };
+
+        if config.fill_fraction.is_some() {
+            world.relax();
+        }
+
+        world
+    }
+
+    /// Pushes overlapping balls apart with position-only solves, without moving them otherwise
+    ///
+    /// Stops once no pair overlaps by more than `RELAX_TOLERANCE`, or after `MAX_RELAX_PASSES`
+    /// passes for fill fractions too dense to ever stop overlapping
+    fn relax(&mut self) {
+        let resolve_mode = std::mem::replace(&mut self.resolve_mode, ResolveMode::PositionOnly);
+        // Hard walls still clamp positions while relaxing, but the bounces they add are undone
+        let velocities: Vec<Vector2> = self.balls.iter().map(|ball| ball.velocity).collect();
+
+        for _ in 0..MAX_RELAX_PASSES {
+            let mut iteration = 0;
+            self.solve(0.0, &mut |_, _| {}, &mut iteration);
+
+            let deepest = self
+                .contacts
+                .iter()
+                .map(|contact| {
+                    overlap_depth(&self.balls[contact.id], &self.balls[contact.other_id])
+                })
+                .fold(0.0, Scalar::max);
+
+            if deepest < RELAX_TOLERANCE {
+                break;
+            }
+        }
+
+        self.resolve_mode = resolve_mode;
+        self.contact_impulses.clear();
+        self.clear_contacts();
+        self.iterations = 0;
+
+        for (ball, velocity) in self.balls.iter_mut().zip(velocities) {
+            ball.velocity = velocity;
+            ball.pressure = 0.0;
+            ball.previous_position = ball.position;
+        }
+    }
+
+    /// Starts a `WorldBuilder` from the built-in defaults
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder::default()
+    }
+
+    /// Switches to the tunable settings of `config`, such as a different profile, keeping the
+    /// balls and bounds as they are
+    pub fn apply_config(&mut self, config: &Config) {
+        self.gravity = config.gravity as Scalar;
+        self.attraction_strength = config.attraction_strength as Scalar;
+        self.gravity_falloff = config.gravity_falloff;
+        self.gravity_center = from_vec2(Vec2::from(config.gravity_center));
+        self.gravity_radius = config.gravity_radius as Scalar;
+        self.gravity_pulse_amplitude = config.gravity_pulse_amplitude as Scalar;
+        self.gravity_pulse_frequency = config.gravity_pulse_frequency as Scalar;
+        self.gravity_scale = config.gravity_scale as Scalar;
+        self.gravity_scale_spread = config.gravity_scale_spread as Scalar;
+        self.resistance = config.resistance as Scalar;
+        self.bounce_amount = config.bounce_amount as Scalar;
+        self.restitution_velocity_threshold = config.restitution_velocity_threshold as Scalar;
+        self.set_max_speed(config.max_speed as Scalar);
+        self.max_pressure = config.max_pressure as Scalar;
+        self.max_correction = config.max_correction as Scalar;
+        self.clamp_contact_impulse = config.clamp_contact_impulse;
+        self.contact_damping = config.contact_damping as Scalar;
+        self.resolve_mode = config.resolve_mode;
+        self.materials = MaterialTable::from_config(&config.material_pairs);
+        self.sim_steps = config.sim_steps;
+        self.convergence_eps = config.convergence_eps as Scalar;
+        self.track_penetration = config.track_penetration;
+        self.walls = config.walls_2d;
+        self.boundary_mode = config.boundary_mode;
+        self.wall_stiffness = config.wall_stiffness as Scalar;
+        self.wall_damping = config.wall_damping as Scalar;
+        self.limit_displacement = config.limit_displacement;
+        self.cohesion_strength = config.cohesion_strength as Scalar;
+        self.interaction_radius = config.interaction_radius as Scalar;
+        self.island_solver = config.island_solver;
+        self.parallel_solver = config.parallel_solver;
+        self.randomize_contact_order = config.randomize_contact_order;
+        self.max_contacts_per_body = config.max_contacts_per_body;
+        self.min_event_impulse = config.min_event_impulse as Scalar;
+        self.event_cooldown = config.event_cooldown as Scalar;
+        self.adaptive_substeps = config.adaptive_substeps;
+        self.cell_ratio_threshold = config.cell_ratio_threshold as Scalar;
+        self.auto_cell_size = config.auto_cell_size;
+        self.substep_fraction = config.substep_fraction as Scalar;
+        self.max_substeps = config.max_substeps;
+        self.max_balls = config.max_balls;
+        self.fixed_dt = config.fixed_dt as Scalar;
+        self.max_steps_per_tick = config.max_steps_per_tick;
+        self.spawn_lifetime = config.spawn_lifetime.map(|lifetime| lifetime as Scalar);
+
+        // Turning cohesion on needs cells spanning the interaction radius
+        self.set_cell_size(self.cell_size());
+    }
+
+    /// Caps the speed of every dynamic ball, and of those added later, at `max_speed`
+    pub fn set_max_speed(&mut self, max_speed: Scalar) {
+        self.max_speed = max_speed;
+
+        for ball in self
+            .balls
+            .iter_mut()
+            .filter(|ball| ball.kind == BodyKind::Dynamic)
+        {
+            ball.max_speed = max_speed;
+        }
+    }
+
+    pub fn bodies(&self) -> &[Ball] {
+        &self.balls
+    }
+
+    /// Adds a ball with a random colour and velocity at `position`, returning its id
+    ///
+    /// It expires after `spawn_lifetime` seconds, if set
+    pub fn spawn_ball(&mut self, position: Vector2) -> usize {
+        let id = self.balls.len();
+
+        self.balls.push(Ball {
+            id,
+            position,
+            velocity: random_velocity(&mut self.rng),
+            pressure: 0.0,
+            color: random_color(&mut self.rng),
+            radius: self.ball_radius,
+            acceleration: Vector2::ZERO,
+            kind: BodyKind::Dynamic,
+            age: 0.0,
+            lifetime: self.spawn_lifetime,
+            previous_position: position,
+            gravity_scale: random_gravity_scale(
+                self.gravity_scale,
+                self.gravity_scale_spread,
+                &mut self.rng,
+            ),
+            max_speed: self.max_speed,
+            user_data: 0,
+            material_id: 0,
+        });
+
+        id
+    }
+
+    /// Adds a kinematic ball, such as a paddle or piston, and returns its id
+    ///
+    /// Its velocity is only ever changed by the caller. The id shifts if balls before it are removed
+    pub fn add_kinematic_ball(
+        &mut self,
+        position: Vector2,
+        velocity: Vector2,
+        radius: Scalar,
+    ) -> usize {
+        let id = self.balls.len();
+
+        self.balls.push(Ball {
+            id,
+            position,
+            velocity,
+            pressure: 0.0,
+            color: WHITE,
+            radius,
+            acceleration: Vector2::ZERO,
+            kind: BodyKind::Kinematic,
+            age: 0.0,
+            lifetime: None,
+            previous_position: position,
+            gravity_scale: 1.0,
+            max_speed: Scalar::INFINITY,
+            user_data: 0,
+            material_id: 0,
+        });
+
+        id
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            balls: self.balls.clone(),
+            container_offset: self.container_offset,
+            sim_time: self.sim_time,
+        }
+    }
+
+    /// Puts the balls, container and simulated time back as they were in `snapshot`
+    ///
+    /// Warm starting starts over, and bonds and collision callbacks of balls that didn't exist
+    /// yet are dropped. Emitters, black holes and drains stay as they are now
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.balls.clone_from(&snapshot.balls);
+        self.container_offset = snapshot.container_offset;
+        self.sim_time = snapshot.sim_time;
+        self.forget_balls();
+    }
+
+    /// Swaps every ball for `balls`, numbering them in order, e.g. to start from a scene loaded
+    /// with `load_balls_csv`
+    ///
+    /// Bonds and collision callbacks of ids past the new count are dropped
+    pub fn replace_balls(&mut self, balls: Vec<Ball>) {
+        self.balls = balls;
+        for (id, ball) in self.balls.iter_mut().enumerate() {
+            ball.id = id;
+        }
+        self.forget_balls();
+    }
+
+    // Clears whatever was tracked about the previous balls after they are swapped out wholesale
+    fn forget_balls(&mut self) {
+        let count = self.balls.len();
+        self.contact_impulses.clear();
+        self.clear_contacts();
+        self.collision_events.clear();
+        self.bonds
+            .retain(|&(id, other_id), _| id < count && other_id < count);
+        self.collision_callbacks.retain(|&id, _| id < count);
+        self.event_cooldowns.resize(count, 0.0);
+        self.spatial_hash.clear();
+    }
+
+    /// Removes every ball closer than `dist` to `position` and reindexes the rest
+    pub fn remove_balls_near(&mut self, position: Vector2, dist: Scalar) {
+        self.remove_balls_where(|ball| ball.position.distance(position) < dist);
+    }
+
+    /// Removes every ball matching `predicate` and reindexes the rest, keeping their contacts
+    pub fn remove_balls_where(&mut self, mut predicate: impl FnMut(&Ball) -> bool) {
+        let mut new_ids = Vec::with_capacity(self.balls.len());
+        let mut next_id = 0;
+
+        for ball in &self.balls {
+            if predicate(ball) {
+                new_ids.push(None);
+            } else {
+                new_ids.push(Some(next_id));
+                next_id += 1;
+            }
+        }
+
+        if next_id == self.balls.len() {
+            return;
+        }
+
+        self.removed_count += self.balls.len() - next_id;
+        self.balls.retain(|ball| new_ids[ball.id].is_some());
+
+        for (idx, ball) in self.balls.iter_mut().enumerate() {
+            ball.id = idx;
+        }
+
+        // Removal keeps the survivors in order, so remapped pairs stay smallest id first
+        self.contact_impulses = std::mem::take(&mut self.contact_impulses)
+            .into_iter()
+            .filter_map(|((id, other_id), impulse)| {
+                Some(((new_ids[id]?, new_ids[other_id]?), impulse))
+            })
+            .collect();
+        self.clear_contacts();
+
+        self.bonds = std::mem::take(&mut self.bonds)
+            .into_iter()
+            .filter_map(|((id, other_id), rest_length)| {
+                Some(((new_ids[id]?, new_ids[other_id]?), rest_length))
+            })
+            .collect();
+
+        self.collision_events = std::mem::take(&mut self.collision_events)
+            .into_iter()
+            .filter_map(|event| {
+                Some(Contact {
+                    id: new_ids[event.id]?,
+                    other_id: new_ids[event.other_id]?,
+                    impulse: event.impulse,
+                })
+            })
+            .collect();
+
+        // Callbacks of removed balls are dropped
+        self.collision_callbacks = std::mem::take(&mut self.collision_callbacks)
+            .into_iter()
+            .filter_map(|(id, callback)| Some((new_ids[id]?, callback)))
+            .collect();
+
+        let mut cooldown_ids = new_ids.iter();
+        self.event_cooldowns
+            .retain(|_| cooldown_ids.next().is_some_and(Option::is_some));
+
+        // Hashed ids no longer match, the next solve re-inserts every ball
+        self.spatial_hash.clear();
+    }
+
+    /// Total balls removed by drains since the world was created, e.g. to measure the flow rate
+    /// through a hopper
+    pub fn drained_count(&self) -> usize {
+        self.drained_count
+    }
+
+    /// Total balls removed since the world was created. Removal reindexes the balls, so an id
+    /// held from before this last changed may now point at a different ball
+    pub fn removed_count(&self) -> usize {
+        self.removed_count
+    }
+
+    /// Advances the simulation by `real_dt` seconds of host time in whole steps of `fixed_dt`,
+    /// carrying the remainder over to the next call
+    ///
+    /// Returns how far the leftover time is into the next step, to draw each ball at
+    /// `interpolated_position(alpha)`. At most `max_steps_per_tick` steps are taken, dropping
+    /// the backlog if the host falls too far behind
+    pub fn tick(&mut self, real_dt: Scalar) -> Scalar {
+        self.accumulator += real_dt.max(0.0);
+
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_dt && steps < self.max_steps_per_tick {
+            self.step(self.fixed_dt);
+            self.accumulator -= self.fixed_dt;
+            steps += 1;
+        }
+
+        if steps == self.max_steps_per_tick {
+            self.accumulator = self.accumulator.min(self.fixed_dt);
+        }
+
+        (self.accumulator / self.fixed_dt).min(1.0)
+    }
+
+    /// Seconds of simulated time stepped since the world was made or last `reset`
+    ///
+    /// This advances by each substep's `dt`, so it follows the simulation rather than the wall
+    /// clock and is the same on every run with the same steps
+    pub fn time(&self) -> f64 {
+        self.sim_time
+    }
+
+    /// Starts the clock over at zero, dropping any partial step `tick` has banked
+    pub fn reset(&mut self) {
+        self.sim_time = 0.0;
+        self.accumulator = 0.0;
+    }
+
+    /// Advances the simulation by one frame of `dt` seconds
+    ///
+    /// With `adaptive_substeps` the frame is split into enough equal substeps that the fastest
+    /// ball covers at most `substep_fraction` of a radius in each, up to `max_substeps`
+    pub fn step(&mut self, dt: Scalar) {
+        self.step_with(dt, |_, _| {});
+    }
+
+    /// Steps like `step`, calling `on_iteration` with the world and a running index after every
+    /// solver iteration, e.g. to draw or record how the solver converges within a frame
+    pub fn step_with(&mut self, dt: Scalar, mut on_iteration: impl FnMut(&World2D, usize)) {
+        self.emit(dt);
+
+        for ball in self.balls.iter_mut() {
+            ball.acceleration = Vector2::ZERO;
+            ball.previous_position = ball.position;
+        }
+
+        self.substeps = if self.adaptive_substeps {
+            self.required_substeps(dt)
+        } else {
+            1
+        };
+        let substep_dt = dt / self.substeps as Scalar;
+
+        let mut iteration = 0;
+        self.iterations = 0;
+
+        for _ in 0..self.substeps {
+            self.solve(substep_dt, &mut on_iteration, &mut iteration);
+            self.integrate(substep_dt);
+            self.sim_time += substep_dt as f64;
+        }
+
+        self.total_iterations += self.iterations;
+        self.step_count += 1;
+
+        // Each substep recorded its changes over the shorter substep, so average them over the frame
+        for ball in self.balls.iter_mut() {
+            ball.acceleration /= self.substeps as Scalar;
+            ball.age += dt;
+        }
+
+        // Before removal, which reindexes the balls the events refer to
+        self.record_collision_events(dt);
+
+        if self.track_penetration {
+            self.peak_penetration = self.peak_penetration.max(self.contact_overlap());
+        }
+
+        self.remove_expired();
+        self.consume();
+        self.drain();
+
+        // After removal, so the callbacks see the ids the balls have now
+        self.run_collision_callbacks();
+    }
+
+    fn run_collision_callbacks(&mut self) {
+        if self.collision_callbacks.is_empty() {
+            return;
+        }
+
+        for event in &self.collision_events {
+            for id in [event.id, event.other_id] {
+                if let Some(callback) = self.collision_callbacks.get_mut(&id) {
+                    callback(*event);
+                }
+            }
+        }
+    }
+
+    /// Spawns whatever each emitter is due this step, until the world holds `max_balls`
+    fn emit(&mut self, dt: Scalar) {
+        for emitter in self.emitters.iter_mut() {
+            for _ in 0..emitter.due(dt) {
+                if self.balls.len() >= self.max_balls {
+                    break;
+                }
+
+                self.balls.push(Ball {
+                    id: self.balls.len(),
+                    position: emitter.position,
+                    velocity: emitter.emit_velocity(&mut self.rng),
+                    pressure: 0.0,
+                    color: random_color(&mut self.rng),
+                    radius: self.ball_radius,
+                    acceleration: Vector2::ZERO,
+                    kind: BodyKind::Dynamic,
+                    age: 0.0,
+                    lifetime: Some(emitter.lifetime),
+                    previous_position: emitter.position,
+                    gravity_scale: random_gravity_scale(
+                        self.gravity_scale,
+                        self.gravity_scale_spread,
+                        &mut self.rng,
+                    ),
+                    max_speed: self.max_speed,
+                    user_data: 0,
+                    material_id: 0,
+                });
+            }
+        }
+    }
+
+    /// Keeps the contacts of the last step hard enough to report, skipping balls still cooling
+    /// down from an earlier event
+    fn record_collision_events(&mut self, dt: Scalar) {
+        self.event_cooldowns.resize(self.balls.len(), 0.0);
+        for cooldown in self.event_cooldowns.iter_mut() {
+            *cooldown = (*cooldown - dt).max(0.0);
+        }
+
+        self.collision_events.clear();
+
+        for contact in &self.contacts {
+            if contact.impulse < self.min_event_impulse
+                || self.event_cooldowns[contact.id] > 0.0
+                || self.event_cooldowns[contact.other_id] > 0.0
+            {
+                continue;
+            }
+
+            self.event_cooldowns[contact.id] = self.event_cooldown;
+            self.event_cooldowns[contact.other_id] = self.event_cooldown;
+            self.collision_events.push(*contact);
+        }
+    }
+
+    /// Removes balls that have outlived their lifetime, or that have one and left the bounds
+    fn remove_expired(&mut self) {
+        let Container { min, max, .. } = self.container();
+
+        self.remove_balls_where(|ball| {
+            let Some(lifetime) = ball.lifetime else {
+                return false;
+            };
+
+            let outside = ball.position.x < min.x - ball.radius
+                || ball.position.x > max.x + ball.radius
+                || ball.position.y < min.y - ball.radius
+                || ball.position.y > max.y + ball.radius;
+
+            ball.age > lifetime || outside
+        });
+    }
+
+    /// Removes balls inside a black hole's horizon, feeding their mass to holes that accrete
+    fn consume(&mut self) {
+        if self.black_holes.is_empty() {
+            return;
+        }
+
+        // Taken out so the holes can grow while `remove_balls_where` borrows the world
+        let mut black_holes = std::mem::take(&mut self.black_holes);
+
+        self.remove_balls_where(|ball| {
+            let Some(hole) = black_holes.iter_mut().find(|hole| hole.consumes(ball)) else {
+                return false;
+            };
+
+            if hole.accretes {
+                hole.strength += ball.mass();
+            }
+
+            true
+        });
+
+        self.black_holes = black_holes;
+    }
+
+    /// Removes balls inside any drain, counting them towards `drained_count`
+    fn drain(&mut self) {
+        if self.drains.is_empty() {
+            return;
+        }
+
+        let removed_before = self.removed_count;
+        let drains = std::mem::take(&mut self.drains);
+
+        self.remove_balls_where(|ball| drains.iter().any(|drain| drain.drains(ball)));
+
+        self.drains = drains;
+        self.drained_count += self.removed_count - removed_before;
+    }
+
+    // Each ball may move `substep_fraction` of its own radius per substep, so a small fast ball
+    // can need more substeps than a larger, faster one
+    fn required_substeps(&self, dt: Scalar) -> usize {
+        let most_radii = self
+            .balls
+            .iter()
+            .map(|ball| ball.velocity.length() * dt / ball.radius)
+            .fold(0.0, Scalar::max);
+
+        let substeps = (most_radii / self.substep_fraction).ceil() as usize;
+
+        substeps.clamp(1, self.max_substeps.max(1))
+    }
+
+    pub fn cell_size(&self) -> Scalar {
+        self.spatial_hash.cell_size()
+    }
+
+    /// Rebuilds the spatial hash with a new cell size, raised if needed to the largest ball
+    /// diameter, since smaller cells would miss contacts between balls two cells apart. With
+    /// cohesion on it is also raised to `interaction_radius`, or cohesive pairs would be missed
+    pub fn set_cell_size(&mut self, cell_size: Scalar) {
+        let mut min_cell_size = self
+            .balls
+            .iter()
+            .map(|ball| ball.radius * 2.0)
+            .fold(Scalar::EPSILON, Scalar::max);
+        if self.cohesion_strength > 0.0 {
+            min_cell_size = min_cell_size.max(self.interaction_radius);
+        }
+
+        self.spatial_hash
+            .set_cell_size(cell_size.max(min_cell_size));
+
+        let positions: Vec<(usize, Vector2)> = self
+            .balls
+            .iter()
+            .map(|ball| (ball.id, ball.position))
+            .collect();
+        self.spatial_hash.update_all(&positions);
+    }
+
+    /// Solver iterations the last step ran, summed over its substeps. With `island_solver` each
+    /// substep counts the iterations of its slowest island
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Mean of `iterations` over every step so far, which drops below `sim_steps` when
+    /// `convergence_eps` lets easy steps finish early
+    pub fn average_iterations(&self) -> Scalar {
+        if self.step_count == 0 {
+            0.0
+        } else {
+            self.total_iterations as Scalar / self.step_count as Scalar
+        }
+    }
+
+    /// Deepest overlap left between touching balls after any step since tracking started, see
+    /// `track_penetration`
+    pub fn peak_penetration(&self) -> Scalar {
+        self.peak_penetration
+    }
+
+    /// Starts `peak_penetration` again from zero, e.g. to try another `sim_steps`
+    pub fn reset_peak_penetration(&mut self) {
+        self.peak_penetration = 0.0;
+    }
+
+    /// Number of substeps the last call to `step` was split into
+    pub fn substeps(&self) -> usize {
+        self.substeps
+    }
+
+    /// Steps like `step`, then checks that the total momentum hasn't drifted, returning the drift
+    ///
+    /// Collisions on their own conserve momentum exactly, so this only holds with gravity, drag
+    /// and the attractor off, no black holes or kinematic balls and no ball touching a wall. In
+    /// debug builds a drift beyond floating-point tolerance panics
+    pub fn step_checked(&mut self, dt: Scalar) -> Scalar {
+        let before = self.momentum();
+        let total_speed: Scalar = self.balls.iter().map(|ball| ball.velocity.length()).sum();
+
+        self.step(dt);
+
+        let drift = self.momentum().distance(before);
+        let tolerance = MOMENTUM_TOLERANCE * (1.0 + total_speed);
+
+        debug_assert!(
+            drift <= tolerance,
+            "momentum drifted by {} (tolerance {}) from {:?} to {:?}",
+            drift,
+            tolerance,
+            before,
+            self.momentum()
+        );
+
+        drift
+    }
+
+    fn solve(
+        &mut self,
+        dt: Scalar,
+        on_iteration: &mut impl FnMut(&World2D, usize),
+        iteration: &mut usize,
+    ) {
+        let balls = &mut self.balls;
+
+        let positions: Vec<(usize, Vector2)> =
+            balls.iter().map(|ball| (ball.id, ball.position)).collect();
+        self.spatial_hash.update_all(&positions);
+
+        if positions.len() >= MIN_BALLS_FOR_CELL_CHECK
+            && self.spatial_hash.occupancy_ratio() > self.cell_ratio_threshold
+        {
+            if !self.cell_warning_logged {
+                eprintln!(
+                    "{} of {} balls have a hash cell to themselves, cell_size {} is probably too small",
+                    self.spatial_hash.occupied_cells(),
+                    positions.len(),
+                    self.spatial_hash.cell_size()
+                );
+                self.cell_warning_logged = true;
+            }
+
+            // Bigger cells only cost more candidate pairs, so growing is always safe
+            let max_cell_size = self.width.max(self.height);
+            if self.auto_cell_size && self.spatial_hash.cell_size() < max_cell_size {
+                let cell_size = (self.spatial_hash.cell_size() * 2.0).min(max_cell_size);
+                self.spatial_hash.set_cell_size(cell_size);
+                self.spatial_hash.update_all(&positions);
+            }
+        }
+
+        // Pressure is rebuilt from this step's contacts, so a ball only loses it by losing contact
+        for ball in balls.iter_mut() {
+            ball.pressure = 0.0;
+        }
+
+        if self.cohesion_strength > 0.0 {
+            for (id, other_id) in self.spatial_hash.candidate_pairs() {
+                let (ball, other_ball) = pair_mut(balls, id, other_id);
+
+                apply_cohesion(
+                    ball,
+                    other_ball,
+                    self.cohesion_strength,
+                    self.interaction_radius,
+                    dt,
+                );
+            }
+        }
+
+        // Taken out for the solve so the settings can borrow it while the balls are mutated
+        let materials = std::mem::take(&mut self.materials);
+        let settings = ContactSettings {
+            bounce_amount: self.bounce_amount,
+            restitution_velocity_threshold: self.restitution_velocity_threshold,
+            max_correction: self.max_correction,
+            clamp_contact_impulse: self.clamp_contact_impulse,
+            contact_damping: self.contact_damping,
+            resolve_mode: self.resolve_mode,
+            materials: &materials,
+        };
+
+        let mut warm_impulses = HashMap::new();
+        for ((id, other_id), impulse) in std::mem::take(&mut self.contact_impulses) {
+            let (ball, other_ball) = pair_mut(balls, id, other_id);
+
+            if is_colliding(ball, other_ball)
+                && filter_allows(&self.collision_filter, ball, other_ball)
+            {
+                let impulse = warm_start_impulse(ball, other_ball, impulse, settings);
+                warm_start(ball, other_ball, impulse, dt);
+                self.contact_impulses.insert((id, other_id), impulse);
+                if self.clamp_contact_impulse {
+                    warm_impulses.insert((id, other_id), impulse);
+                }
+            }
+        }
+
+        self.iterations += if self.island_solver {
+            self.solve_islands(dt, settings, on_iteration, iteration)
+        } else if self.parallel_solver {
+            self.solve_colored(dt, settings, on_iteration, iteration)
+        } else {
+            self.solve_all(dt, settings, on_iteration, iteration)
+        };
+
+        if self.resolve_mode != ResolveMode::PositionOnly {
+            for &(id, other_id) in self.contact_impulses.keys() {
+                let (ball, other_ball) = pair_mut(&mut self.balls, id, other_id);
+                damp_contact(ball, other_ball, settings, self.substeps, dt);
+            }
+        }
+
+        // Every pair that overlapped at some point this step has an entry, even if its impulse is zero
+        self.contacts = self
+            .contact_impulses
+            .iter()
+            .map(|(&(id, other_id), &impulse)| Contact {
+                id,
+                other_id,
+                impulse,
+            })
+            .collect();
+
+        if self.clamp_contact_impulse {
+            for (&(id, other_id), impulse) in self.contact_impulses.iter_mut() {
+                let warm_impulse = warm_impulses.get(&(id, other_id)).copied().unwrap_or(0.0);
+                let (ball, other_ball) = (&self.balls[id], &self.balls[other_id]);
+                *impulse = stopping_impulse(ball, other_ball, warm_impulse, *impulse, settings);
+            }
+        }
+        self.materials = materials;
+
+        self.contact_impulses.retain(|_, impulse| *impulse > 0.0);
+        self.index_contacts();
+
+        if self.resolve_mode == ResolveMode::Sticky {
+            for contact in &self.contacts {
+                let (ball, other_ball) = (&self.balls[contact.id], &self.balls[contact.other_id]);
+                let rest_length = ball.radius + other_ball.radius;
+
+                self.bonds
+                    .entry(pair_key(contact.id, contact.other_id))
+                    .or_insert(rest_length);
+            }
+        }
+
+        self.solve_bonds(dt);
+
+        for ball in self.balls.iter_mut() {
+            ball.pressure = ball.pressure.clamp(0.0, self.max_pressure);
+        }
+    }
+
+    fn clear_contacts(&mut self) {
+        self.contacts.clear();
+        self.ball_contacts.clear();
+        self.ball_contact_starts.clear();
+    }
+
+    /// Groups the contacts by ball, counting each ball's contacts and then placing them at the
+    /// right offset, which keeps the order of `contacts` within each group
+    fn index_contacts(&mut self) {
+        let starts = &mut self.ball_contact_starts;
+        starts.clear();
+        starts.resize(self.balls.len() + 1, 0);
+
+        for contact in &self.contacts {
+            starts[contact.id + 1] += 1;
+            starts[contact.other_id + 1] += 1;
+        }
+        for id in 1..starts.len() {
+            starts[id] += starts[id - 1];
+        }
+
+        let placeholder = Contact {
+            id: 0,
+            other_id: 0,
+            impulse: 0.0,
+        };
+        self.ball_contacts.clear();
+        self.ball_contacts
+            .resize(self.contacts.len() * 2, placeholder);
+
+        let mut next = starts.clone();
+        for contact in &self.contacts {
+            let flipped = Contact {
+                id: contact.other_id,
+                other_id: contact.id,
+                impulse: contact.impulse,
+            };
+
+            for contact in [*contact, flipped] {
+                self.ball_contacts[next[contact.id]] = contact;
+                next[contact.id] += 1;
+            }
+        }
+    }
+
+    /// Runs `sim_steps` passes over every bond, after the contacts so bonds have the last word
+    fn solve_bonds(&mut self, dt: Scalar) {
+        if self.bonds.is_empty() {
+            return;
+        }
+
+        for _ in 0..self.sim_steps {
+            for (&(id, other_id), &rest_length) in &self.bonds {
+                let (ball, other_ball) = pair_mut(&mut self.balls, id, other_id);
+                resolve_bond(ball, other_ball, rest_length, dt);
+            }
+        }
+    }
+
+    /// Runs every solver iteration over all candidate pairs at once, returning how many it ran
+    fn solve_all(
+        &mut self,
+        dt: Scalar,
+        settings: ContactSettings<'_>,
+        on_iteration: &mut impl FnMut(&World2D, usize),
+        iteration: &mut usize,
+    ) -> usize {
+        let mut pairs = self.capped_pairs(self.spatial_hash.candidate_pairs().collect());
+        let container = self.container();
+        let mut iterations = 0;
+
+        for _ in 0..self.sim_steps {
+            if self.randomize_contact_order {
+                self.rng.shuffle(&mut pairs);
+            }
+
+            // Borrowed afresh each iteration so the world can be handed to `on_iteration`
+            let balls = &mut self.balls;
+            let mut largest_correction: Scalar = 0.0;
+
+            for &(id, other_id) in &pairs {
+                let (ball, other_ball) = pair_mut(balls, id, other_id);
+
+                if is_colliding(ball, other_ball)
+                    && filter_allows(&self.collision_filter, ball, other_ball)
+                {
+                    let accumulated_impulse = self
+                        .contact_impulses
+                        .entry(pair_key(id, other_id))
+                        .or_insert(0.0);
+
+                    let correction =
+                        resolve_collision(ball, other_ball, settings, accumulated_impulse, dt);
+                    largest_correction = largest_correction.max(correction);
+                }
+            }
+
+            if self.boundary_mode == BoundaryMode::Hard {
+                for ball in balls
+                    .iter_mut()
+                    .filter(|ball| ball.kind == BodyKind::Dynamic)
+                {
+                    resolve_boundaries(
+                        ball,
+                        container,
+                        &self.walls,
+                        self.restitution_velocity_threshold,
+                        dt,
+                    );
+                }
+            }
+
+            on_iteration(self, *iteration);
+            *iteration += 1;
+            iterations += 1;
+
+            if largest_correction < self.convergence_eps {
+                break;
+            }
+        }
+
+        iterations
+    }
+
+    /// Groups touching balls into islands and runs all the solver iterations for one island
+    /// before moving to the next, so each pile converges on its own
+    ///
+    /// `on_iteration` is called after every iteration of every island. Returns the most
+    /// iterations any island ran
+    fn solve_islands(
+        &mut self,
+        dt: Scalar,
+        settings: ContactSettings<'_>,
+        on_iteration: &mut impl FnMut(&World2D, usize),
+        iteration: &mut usize,
+    ) -> usize {
+        let pairs = self.capped_pairs(self.nearby_pairs(settings.max_correction));
+        let mut islands = build_islands(self.balls.len(), &pairs);
+        self.island_count = islands.len();
+        let container = self.container();
+        let mut most_iterations = 0;
+
+        for island in &mut islands {
+            // A lone ball only has walls to deal with, and a second pass over them changes nothing
+            let iterations = if island.pairs.is_empty() {
+                1
+            } else {
+                self.sim_steps
+            };
+
+            for island_iteration in 1..=iterations {
+                if self.randomize_contact_order {
+                    self.rng.shuffle(&mut island.pairs);
+                }
+
+                let mut largest_correction: Scalar = 0.0;
+
+                for &(id, other_id) in &island.pairs {
+                    let (ball, other_ball) = pair_mut(&mut self.balls, id, other_id);
+
+                    if is_colliding(ball, other_ball)
+                        && filter_allows(&self.collision_filter, ball, other_ball)
+                    {
+                        let accumulated_impulse = self
+                            .contact_impulses
+                            .entry(pair_key(id, other_id))
+                            .or_insert(0.0);
+
+                        let correction =
+                            resolve_collision(ball, other_ball, settings, accumulated_impulse, dt);
+                        largest_correction = largest_correction.max(correction);
+                    }
+                }
+
+                if self.boundary_mode == BoundaryMode::Hard {
+                    for &id in &island.balls {
+                        let ball = &mut self.balls[id];
+
+                        if ball.kind == BodyKind::Dynamic {
+                            resolve_boundaries(
+                                ball,
+                                container,
+                                &self.walls,
+                                self.restitution_velocity_threshold,
+                                dt,
+                            );
+                        }
+                    }
+                }
+
+                on_iteration(self, *iteration);
+                *iteration += 1;
+                most_iterations = most_iterations.max(island_iteration as usize);
+
+                if largest_correction < self.convergence_eps {
+                    break;
+                }
+            }
+        }
+
+        most_iterations
+    }
+
+    /// Resolves the pairs one colour class at a time, every pair in a class in parallel
+    ///
+    /// Pairs in a class share no balls, so resolving them from a snapshot and writing the results
+    /// back gives exactly what resolving them one by one would, in any thread order. Returns how
+    /// many iterations it ran
+    fn solve_colored(
+        &mut self,
+        dt: Scalar,
+        settings: ContactSettings<'_>,
+        on_iteration: &mut impl FnMut(&World2D, usize),
+        iteration: &mut usize,
+    ) -> usize {
+        let pairs = self.capped_pairs(self.nearby_pairs(settings.max_correction));
+        let classes = color_pairs(self.balls.len(), &pairs);
+        self.color_count = classes.len();
+        let mut iterations = 0;
+
+        for _ in 0..self.sim_steps {
+            let mut largest_correction: Scalar = 0.0;
+
+            for class in &classes {
+                let balls = &self.balls;
+                let contact_impulses = &self.contact_impulses;
+                let collision_filter = &self.collision_filter;
+
+                let resolved: Vec<(Ball, Ball, Option<Scalar>, Scalar)> = class
+                    .par_iter()
+                    .map(|&(id, other_id)| {
+                        let (mut ball, mut other_ball) = (balls[id], balls[other_id]);
+
+                        if !is_colliding(&ball, &other_ball)
+                            || !filter_allows(collision_filter, &ball, &other_ball)
+                        {
+                            return (ball, other_ball, None, 0.0);
+                        }
+
+                        let key = pair_key(id, other_id);
+                        let mut impulse = contact_impulses.get(&key).copied().unwrap_or(0.0);
+                        let correction = resolve_collision(
+                            &mut ball,
+                            &mut other_ball,
+                            settings,
+                            &mut impulse,
+                            dt,
+                        );
+
+                        (ball, other_ball, Some(impulse), correction)
+                    })
+                    .collect();
+
+                for (ball, other_ball, impulse, correction) in resolved {
+                    largest_correction = largest_correction.max(correction);
+
+                    if let Some(impulse) = impulse {
+                        self.contact_impulses
+                            .insert(pair_key(ball.id, other_ball.id), impulse);
+                    }
+
+                    self.balls[ball.id] = ball;
+                    self.balls[other_ball.id] = other_ball;
+                }
+            }
+
+            if self.boundary_mode == BoundaryMode::Hard {
+                let (container, walls) = (self.container(), self.walls);
+                let threshold = self.restitution_velocity_threshold;
+
+                self.balls
+                    .par_iter_mut()
+                    .filter(|ball| ball.kind == BodyKind::Dynamic)
+                    .for_each(|ball| resolve_boundaries(ball, container, &walls, threshold, dt));
+            }
+
+            on_iteration(self, *iteration);
+            *iteration += 1;
+            iterations += 1;
+
+            if largest_correction < self.convergence_eps {
+                break;
+            }
+        }
+
+        iterations
+    }
+
+    /// Candidate pairs close enough that a correction elsewhere could push them together this step
+    fn nearby_pairs(&self, margin: Scalar) -> Vec<(usize, usize)> {
+        self.spatial_hash
+            .candidate_pairs()
+            .filter(|&(id, other_id)| {
+                let (ball, other_ball) = (&self.balls[id], &self.balls[other_id]);
+                let reach = ball.radius + other_ball.radius + margin;
+
+                ball.position.distance_squared(other_ball.position) < reach * reach
+            })
+            .collect()
+    }
+
+    /// `pairs` limited to `max_contacts_per_body` per ball, if set
+    fn capped_pairs(&self, pairs: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        match self.max_contacts_per_body {
+            Some(max_contacts) => cap_contacts(&self.balls, pairs, max_contacts),
+            None => pairs,
+        }
+    }
+
+    /// Number of colour classes the contacts were split into when `parallel_solver` is on
+    pub fn color_count(&self) -> usize {
+        self.color_count
+    }
+
+    /// Number of islands the last step was split into when `island_solver` is on
+    pub fn island_count(&self) -> usize {
+        self.island_count
+    }
+
+    fn integrate(&mut self, dt: Scalar) {
+        self.container_offset += self.container_velocity * dt;
+        let container = self.container();
+        let gravity_strength = self.gravity_strength();
+        // Substeps that each keep resistance^(1/n) of the velocity keep `resistance` between them
+        let resistance = self.resistance.powf((self.substeps as Scalar).recip());
+
+        for ball in self.balls.iter_mut() {
+            if ball.kind == BodyKind::Kinematic {
+                ball.position += ball.velocity * dt;
+                continue;
+            }
+
+            if let Some(attractor) = self.attractor {
+                let mut force = attractor - ball.position;
+
+                let distance = force.length();
+                if distance < 0.1 {
+                    force /= distance;
+                }
+
+                ball.accelerate(force * self.attraction_strength * dt, dt);
+            }
+
+            for hole in &self.black_holes {
+                ball.accelerate(hole.pull(ball.position) * dt, dt);
+            }
+
+            if self.do_gravity {
+                let gravity = gravity_at(
+                    self.gravity_falloff,
+                    gravity_strength,
+                    self.gravity_direction,
+                    self.gravity_center,
+                    self.gravity_radius,
+                    vector2(self.width, self.height),
+                    ball.position,
+                );
+                ball.accelerate(gravity * ball.gravity_scale * dt, dt);
+            }
+
+            if self.boundary_mode == BoundaryMode::Soft {
+                resolve_soft_boundaries(
+                    ball,
+                    container,
+                    self.wall_stiffness,
+                    self.wall_damping,
+                    dt,
+                );
+            }
+
+            let drag = ball.velocity * (resistance - 1.0);
+            ball.accelerate(drag, dt);
+
+            let speed_limit = ball.velocity.clamp_length_max(ball.max_speed) - ball.velocity;
+            ball.accelerate(speed_limit, dt);
+
+            // Split fast moves so no single move carries a ball further than its own radius
+            let move_count = if self.limit_displacement {
+                let radii = ball.velocity.length() * dt / ball.radius.max(Scalar::EPSILON);
+                (radii.ceil() as usize).clamp(1, MAX_DISPLACEMENT_MOVES)
+            } else {
+                1
+            };
+            let move_dt = dt / move_count as Scalar;
+
+            for _ in 0..move_count {
+                ball.position += ball.velocity * move_dt;
+
+                if move_count > 1 && self.boundary_mode == BoundaryMode::Hard {
+                    // Only used to record the bounce as acceleration, which is averaged over
+                    // the whole of `dt` however many moves it is split into
+                    resolve_boundaries(
+                        ball,
+                        container,
+                        &self.walls,
+                        self.restitution_velocity_threshold,
+                        dt,
+                    );
+                }
+            }
+        }
+    }
+
+    fn container(&self) -> Container {
+        Container {
+            min: self.container_offset,
+            max: self.container_offset + vector2(self.width, self.height),
+            velocity: self.container_velocity,
+        }
+    }
+
+    /// Strength of gravity right now, `gravity` plus wherever the pulse is in its cycle
+    pub fn gravity_strength(&self) -> Scalar {
+        let phase =
+            2.0 * std::f64::consts::PI * self.gravity_pulse_frequency as f64 * self.sim_time;
+        self.gravity + self.gravity_pulse_amplitude * phase.sin() as Scalar
+    }
+
+    /// Acceleration gravity gives a ball at `position`, following `gravity_falloff`
+    pub fn gravity_at(&self, position: Vector2) -> Vector2 {
+        gravity_at(
+            self.gravity_falloff,
+            self.gravity_strength(),
+            self.gravity_direction,
+            self.gravity_center,
+            self.gravity_radius,
+            vector2(self.width, self.height),
+            position,
+        )
+    }
+
+    /// Total kinetic energy of every ball, treating each as unit mass
+    pub fn kinetic_energy(&self) -> Scalar {
+        self.balls
+            .iter()
+            .map(|ball| 0.5 * ball.velocity.length_squared())
+            .sum()
+    }
+
+    /// Total momentum of every ball, treating each as unit mass
+    pub fn momentum(&self) -> Vector2 {
+        self.balls.iter().map(|ball| ball.velocity).sum()
+    }
+
+    /// Number of ball pairs that pushed on each other during the last step
+    pub fn contact_count(&self) -> usize {
+        self.contact_impulses.len()
+    }
+
+    /// Bonds the pair at their current distance, replacing any bond already between them
+    pub fn bond(&mut self, id: usize, other_id: usize) {
+        assert_ne!(id, other_id, "A ball can't be bonded to itself");
+
+        let rest_length = self.balls[id]
+            .position
+            .distance(self.balls[other_id].position);
+        self.bonds.insert(pair_key(id, other_id), rest_length);
+    }
+
+    /// Every bond between two balls, whether made with `bond` or by touching in `Sticky` mode
+    pub fn bonds(&self) -> impl Iterator<Item = Bond> + '_ {
+        self.bonds
+            .iter()
+            .map(|(&(id, other_id), &rest_length)| Bond {
+                id,
+                other_id,
+                rest_length,
+            })
+    }
+
+    /// Every pair of balls resolved during the last step
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Contacts of the last step with at least `min_event_impulse`, at most one per ball every
+    /// `event_cooldown` seconds, e.g. to play a sound for each
+    pub fn collision_events(&self) -> &[Contact] {
+        &self.collision_events
+    }
+
+    /// Calls `callback` after every step for each of the step's collision events that involve
+    /// ball `id`, replacing any callback it already had. The callback follows the ball when
+    /// removals change its id, and is dropped when the ball is removed
+    pub fn on_collision(&mut self, id: usize, callback: impl FnMut(Contact) + Send + 'static) {
+        self.collision_callbacks.insert(id, Box::new(callback));
+    }
+
+    pub fn remove_collision_callback(&mut self, id: usize) {
+        self.collision_callbacks.remove(&id);
+    }
+
+    /// Calls `on_render`, if set, with the world as it is now
+    pub fn run_render_hook(&mut self, context: &RenderContext) {
+        // Taken out for the call so the hook can be handed the world it belongs to
+        if let Some(mut hook) = self.on_render.take() {
+            hook(self, context);
+            self.on_render = Some(hook);
+        }
+    }
+
+    /// Contacts of ball `id` from the last step, each with `id` as its first ball
+    ///
+    /// The contacts are grouped by ball once per step, so this is only a slice lookup
+    pub fn contacts_for(&self, id: usize) -> &[Contact] {
+        match self.ball_contact_starts.get(id..id + 2) {
+            Some(&[start, end]) => &self.ball_contacts[start..end],
+            _ => &[],
+        }
+    }
+
+    /// Ids of the balls that `id` was resolved against during the last step
+    pub fn contact_ids(&self, id: usize) -> Vec<usize> {
+        self.contacts_for(id)
+            .iter()
+            .map(|contact| contact.other_id)
+            .collect()
+    }
+
+    /// Number of balls sharing or bordering the spatial hash cell of ball `id`
+    pub fn neighbour_count(&self, id: usize) -> usize {
+        self.spatial_hash
+            .get_nearby_objects(self.balls[id].position, id)
+            .len()
+    }
+
+    /// Id of the ball covering `point`, picking the closest centre if several overlap it
+    pub fn ball_at(&self, point: Vector2) -> Option<usize> {
+        self.balls
+            .iter()
+            .filter(|ball| ball.position.distance(point) < ball.radius)
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(point)
+                    .total_cmp(&b.position.distance_squared(point))
+            })
+            .map(|ball| ball.id)
+    }
+
+    /// Checksum of every ball's position and velocity, rounded to `STATE_HASH_QUANTUM`
+    ///
+    /// Two runs of the same scene give the same hash, so recording it after a fixed number of
+    /// steps catches any change that alters the results
+    pub fn state_hash(&self) -> u64 {
+        self.balls
+            .iter()
+            .flat_map(|ball| {
+                [
+                    ball.position.x,
+                    ball.position.y,
+                    ball.velocity.x,
+                    ball.velocity.y,
+                ]
+            })
+            .fold(FNV_OFFSET, |hash, value| {
+                fnv1a(hash, (value / STATE_HASH_QUANTUM).round() as i64)
+            })
+    }
+
+    /// Deepest overlap left between the pairs resolved in the last step
+    ///
+    /// Only checks the solver's contacts rather than every pair, so unlike `max_overlap` it is
+    /// cheap enough to run every step. Pairs that first touched while integrating are caught
+    /// on the next step
+    pub fn contact_overlap(&self) -> Scalar {
+        self.contacts
+            .iter()
+            .map(|contact| overlap_depth(&self.balls[contact.id], &self.balls[contact.other_id]))
+            .fold(0.0, Scalar::max)
+    }
+
+    /// Deepest overlap between any two balls, checked pairwise against current positions
+    pub fn max_overlap(&self) -> Scalar {
+        let mut max_overlap: Scalar = 0.0;
+
+        for (i, ball) in self.balls.iter().enumerate() {
+            for other_ball in &self.balls[i + 1..] {
+                max_overlap = max_overlap.max(overlap_depth(ball, other_ball));
+            }
+        }
+
+        max_overlap
+    }
+}
@@ -1,24 +1,17 @@
 use rust_physics_engine::common;
-mod spatial_hash_3d;
+use rust_physics_engine::version_3d::world::{Ball, World3D};
 
-use common::config::load_config;
+use common::auto_sim_steps::{AutoSimSteps, MAX_SIM_STEPS, MIN_SIM_STEPS};
+use common::color_scale::ColorScale;
+use common::config::load_config_or_default;
 use common::fps_counter::SmoothedFps;
-use spatial_hash_3d::SpatialHash;
+use common::frame_limiter::FrameLimiter;
+use common::legend::draw_legend;
 
 use partial_borrow::prelude::*;
 
 use macroquad::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
-struct Ball {
-    id: usize,
-    position: Vec3,
-    velocity: Vec3,
-    pressure: f32,
-    color: Color,
-    radius: f32,
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DisplayMode {
     Normal,
@@ -26,6 +19,13 @@ enum DisplayMode {
     Pressure,
 }
 
+// Number keys that jump straight to a display mode, in the same order `R` cycles through them
+const DISPLAY_MODE_KEYS: [(KeyCode, DisplayMode); 3] = [
+    (KeyCode::Key1, DisplayMode::Normal),
+    (KeyCode::Key2, DisplayMode::Velocity),
+    (KeyCode::Key3, DisplayMode::Pressure),
+];
+
 struct State {
     display_mode: DisplayMode,
 }
@@ -37,6 +37,10 @@ impl State {
         }
     }
 
+    fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+    }
+
     fn toggle_display_mode(&mut self) {
         self.display_mode = match self.display_mode {
             DisplayMode::Normal => DisplayMode::Velocity,
@@ -46,12 +50,7 @@ impl State {
     }
 }
 
-fn get_color_from_vel(ball: Ball, largest_speed: f32) -> Color {
-    let vel = ball.velocity;
-    let speed = vel.length();
-
-    let normalised_speed = speed / largest_speed;
-
+fn velocity_gradient(normalised_speed: f32) -> Color {
     Color {
         r: (0.0),
         g: (normalised_speed),
@@ -60,15 +59,7 @@ fn get_color_from_vel(ball: Ball, largest_speed: f32) -> Color {
     }
 }
 
-fn get_color_from_pressure(ball: Ball, largest_pressure: f32) -> Color {
-    let pressure = ball.pressure;
-
-    let mut normalised_pressure = 0.0;
-
-    if largest_pressure != 0.0 {
-        normalised_pressure = pressure / largest_pressure;
-    }
-
+fn pressure_gradient(normalised_pressure: f32) -> Color {
     Color {
         r: (normalised_pressure),
         g: (0.0),
@@ -77,220 +68,166 @@ fn get_color_from_pressure(ball: Ball, largest_pressure: f32) -> Color {
     }
 }
 
-fn is_colliding(ball: &Ball, otherball: &Ball) -> bool {
-    let dist = ball.position.distance(otherball.position);
+fn get_color_from_vel(ball: Ball, largest_speed: f32) -> Color {
+    let vel = ball.velocity;
+    let speed = vel.length();
 
-    dist < ball.radius + otherball.radius
-}
+    let normalised_speed = speed / largest_speed;
 
-fn resolve_collision(ball: &mut Ball, otherball: &mut Ball, bounce_amount: f32, max_pressure: f32) {
-    let mut pdiff = otherball.position - ball.position;
+    velocity_gradient(normalised_speed)
+}
 
-    let dist = ball.position.distance(otherball.position);
+fn get_color_from_pressure(ball: Ball, pressure_scale: f32) -> Color {
+    let pressure = ball.pressure;
 
-    let overlap = (ball.radius + otherball.radius) - dist;
+    let mut normalised_pressure = 0.0;
 
-    if overlap < 0.001 {
-        return;
+    if pressure_scale != 0.0 {
+        normalised_pressure = (pressure / pressure_scale).min(1.0);
     }
 
-    pdiff /= dist;
-
-    ball.position -= pdiff * overlap / 2.0;
-    otherball.position += pdiff * overlap / 2.0;
-
-    let vdiff = otherball.velocity - ball.velocity;
+    pressure_gradient(normalised_pressure)
+}
 
-    let dot_product = vdiff.x * pdiff.x + vdiff.y * pdiff.y + vdiff.z * pdiff.z;
+// Keeps the orbit camera just short of straight up or down, where its up vector would flip
+const MAX_ELEVATION: f32 = std::f32::consts::FRAC_PI_2 - 0.05;
+const ORBIT_SPEED: f32 = 0.005; // Radians per pixel dragged
+const PAN_SPEED: f32 = 0.001; // Fraction of the orbit radius per pixel dragged
+
+/// Camera circling `target` at `radius`, turned by `azimuth` about the vertical axis and raised
+/// by `elevation`, both in radians
+struct OrbitCamera {
+    target: Vec3,
+    azimuth: f32,
+    elevation: f32,
+    radius: f32,
+}
 
-    if dot_product > 0.0 {
-        return;
+impl OrbitCamera {
+    fn position(&self) -> Vec3 {
+        // y points down, so raising the camera means a smaller y
+        self.target
+            + self.radius
+                * vec3(
+                    self.elevation.cos() * self.azimuth.cos(),
+                    -self.elevation.sin(),
+                    self.elevation.cos() * self.azimuth.sin(),
+                )
     }
 
-    let force = dot_product * bounce_amount;
-
-    let area = std::f32::consts::PI * ball.radius * ball.radius;
-    let other_area = std::f32::consts::PI * otherball.radius * otherball.radius;
-
-    ball.pressure = -force / area;
-    otherball.pressure = -force / other_area;
-
-    ball.pressure = ball.pressure.min(max_pressure);
-    otherball.pressure = otherball.pressure.min(max_pressure);
+    fn orbit(&mut self, drag: Vec2) {
+        self.azimuth += drag.x * ORBIT_SPEED;
+        self.elevation =
+            (self.elevation + drag.y * ORBIT_SPEED).clamp(-MAX_ELEVATION, MAX_ELEVATION);
+    }
 
-    ball.velocity += force * pdiff;
-    otherball.velocity -= force * pdiff;
-}
+    /// Moves the target across the view so the scene follows the mouse
+    fn pan(&mut self, drag: Vec2) {
+        let forward = (self.target - self.position()).normalize();
+        let right = forward.cross(vec3(0.0, -1.0, 0.0)).normalize();
+        let up = right.cross(forward);
 
-fn resolve_boundaries(
-    ball: &mut Ball,
-    screen_width: f32,
-    screen_height: f32,
-    screen_depth: f32,
-    bounce_amount: f32,
-) {
-    if ball.position.x - ball.radius < 0.0 {
-        ball.position.x = ball.radius;
-        if ball.velocity.x < 0.0 {
-            ball.velocity.x *= -bounce_amount;
-        }
-    } else if ball.position.x + ball.radius > screen_width {
-        ball.position.x = screen_width - ball.radius;
-        if ball.velocity.x > 0.0 {
-            ball.velocity.x *= -bounce_amount;
-        }
+        self.target += (right * drag.x + up * drag.y) * self.radius * PAN_SPEED;
     }
 
-    if ball.position.y - ball.radius < 0.0 {
-        ball.position.y = ball.radius;
-        if ball.velocity.y < 0.0 {
-            ball.velocity.y *= -bounce_amount;
-        }
-    } else if ball.position.y + ball.radius > screen_height {
-        ball.position.y = screen_height - ball.radius;
-        if ball.velocity.y > 0.0 {
-            ball.velocity.y *= -bounce_amount;
-        }
+    fn zoom(&mut self, scroll: f32) {
+        self.radius = (self.radius * 1.1_f32.powf(-scroll.signum())).clamp(10.0, 20000.0);
     }
 
-    if ball.position.z - ball.radius < 0.0 {
-        ball.position.z = ball.radius;
-        if ball.velocity.z < 0.0 {
-            ball.velocity.z *= -bounce_amount;
-        }
-    } else if ball.position.z + ball.radius > screen_depth {
-        ball.position.z = screen_depth - ball.radius;
-        if ball.velocity.z > 0.0 {
-            ball.velocity.z *= -bounce_amount;
+    fn camera(&self) -> Camera3D {
+        Camera3D {
+            position: self.position(),
+            up: vec3(0.0, -1.0, 0.0),
+            target: self.target,
+            ..Default::default()
         }
     }
 }
 
+// How far the farthest balls fade towards the background, short of vanishing into it
+const MAX_FOG: f32 = 0.75;
+
+/// Blends `color` towards `background` the further `distance` lies between `near` and `far`
+fn depth_fog(color: Color, background: Color, distance: f32, near: f32, far: f32) -> Color {
+    let amount = ((distance - near) / (far - near)).clamp(0.0, 1.0) * MAX_FOG;
+
+    Color::new(
+        color.r + (background.r - color.r) * amount,
+        color.g + (background.g - color.g) * amount,
+        color.b + (background.b - color.b) * amount,
+        color.a,
+    )
+}
+
 // #[cfg(feature = "version_3d")]
 #[macroquad::main("Physics Sim")]
 async fn main() {
-    let config = load_config("config.toml");
-
-    let ball_count = config.ball_count_3d;
-    let ball_radius = config.ball_radius;
-    let gravity = config.gravity;
-    let resistance = config.resistance;
-    let bounce_amount = config.bounce_amount;
-    let max_speed = config.max_speed;
-    let max_pressure = config.max_pressure;
+    let config = load_config_or_default("config.toml");
+
     let width = config.width;
     let height = config.height;
     let depth = config.depth;
-    let mut sim_steps = config.sim_steps;
     let auto_sim_steps = config.auto_sim_steps;
-    let target_fps = config.target_fps;
-    let fps_boundary = config.fps_boundary;
-    let delete_dist = config.delete_dist;
+    let mut auto_steps = AutoSimSteps::new(config.target_fps, config.fps_boundary);
+    let _delete_dist = config.delete_dist;
+    let auto_pressure_scale = config.auto_pressure_scale;
+    let pressure_display_max = config.pressure_display_max;
+    let mut speed_color_scale = ColorScale::new(config.color_scale_decay);
+    let mut pressure_color_scale = ColorScale::new(config.color_scale_decay);
+    let mut depth_fog_enabled = config.depth_fog;
+    let background_color = Color::new(
+        config.background_color[0],
+        config.background_color[1],
+        config.background_color[2],
+        1.0,
+    );
 
     request_new_screen_size(width, height);
 
     let mut smoothed_fps = SmoothedFps::new();
+    let mut frame_limiter = FrameLimiter::new(config.max_fps);
 
-    let mut colors: Vec<Color> = (0..ball_count)
-        .map(|_| {
-            Color::new(
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                rand::gen_range(0.0, 1.0),
-                1.0,
-            )
-        })
-        .collect();
-
-    let mut balls: Vec<Ball> = (0..ball_count)
-        .enumerate()
-        .map(|(id, _)| Ball {
-            id,
-            position: Vec3 {
-                x: rand::gen_range(ball_radius, width - ball_radius),
-                y: rand::gen_range(ball_radius, height - ball_radius),
-                z: rand::gen_range(ball_radius, depth - ball_radius),
-            },
-            velocity: Vec3 {
-                x: rand::gen_range(-100.0, 100.0),
-                y: rand::gen_range(-100.0, 100.0),
-                z: rand::gen_range(-100.0, 100.0),
-            },
-            pressure: 0.0,
-            color: colors[id],
-            radius: ball_radius,
-        })
-        .collect();
-
-    let mut spatial_hash: SpatialHash<usize> = SpatialHash::new((ball_radius * 2.0) + 2.0);
-
-    let mut do_gravity = true;
+    let mut world = World3D::new(&config);
 
     let mut display_state = State::new();
 
-    let mut cam_angle: f32 = 90.0;
-    let mut cam_height: f32 = -10.0;
-    let mut cam_pos = vec3(width / 2.0, cam_height, -1000.);
+    // Starts in front of the box, looking along +z like the old fixed camera
+    let mut orbit_camera = OrbitCamera {
+        target: vec3(width / 2.0, height / 2.0, depth / 2.0),
+        azimuth: -std::f32::consts::FRAC_PI_2,
+        elevation: 0.2,
+        radius: width.max(height).max(depth) * 1.5,
+    };
+    let mut last_mouse: Vec2 = mouse_position().into();
 
     loop {
-        clear_background(BLACK);
+        clear_background(background_color);
 
-        if is_key_down(KeyCode::Q) {
-            cam_angle += 0.01;
-        }
+        let mouse: Vec2 = mouse_position().into();
+        let drag = mouse - last_mouse;
+        last_mouse = mouse;
 
-        if is_key_down(KeyCode::E) {
-            cam_angle -= 0.01;
+        if is_mouse_button_down(MouseButton::Right) {
+            orbit_camera.orbit(drag);
         }
 
-        let forward_dir = vec2(cam_angle.cos(), cam_angle.sin());
-
-        if is_key_down(KeyCode::W) {
-            cam_pos.x += forward_dir.x * 10.0;
-            cam_pos.z += forward_dir.y * 10.0;
+        if is_mouse_button_down(MouseButton::Middle) {
+            orbit_camera.pan(drag);
         }
 
-        if is_key_down(KeyCode::S) {
-            cam_pos.x -= forward_dir.x * 10.0;
-            cam_pos.z -= forward_dir.y * 10.0;
+        let scroll = mouse_wheel().1;
+        if scroll != 0.0 {
+            orbit_camera.zoom(scroll);
         }
 
-        let right_dir = vec2(-forward_dir.y, forward_dir.x);
-
-        if is_key_down(KeyCode::A) {
-            cam_pos.x += right_dir.x * 10.0;
-            cam_pos.z += right_dir.y * 10.0;
-        }
-
-        if is_key_down(KeyCode::D) {
-            cam_pos.x -= right_dir.x * 10.0;
-            cam_pos.z -= right_dir.y * 10.0;
-        }
-
-        if is_key_down(KeyCode::LeftShift) {
-            cam_pos.y += 10.0;
-        }
-        if is_key_down(KeyCode::LeftControl) {
-            cam_pos.y -= 10.0;
-        }
-
-        set_camera(&Camera3D {
-            position: cam_pos,
-            up: vec3(0.0, -1.0, 0.0),
-            target: cam_pos + vec3(forward_dir.x, 0.0, forward_dir.y),
-            ..Default::default()
-        });
+        let cam_pos = orbit_camera.position();
+        set_camera(&orbit_camera.camera());
 
         let mut largest_speed: f32 = 0.0;
         let mut largest_pressure: f32 = 0.0;
 
         // let mouse_position: Vec2 = mouse_position().into();
 
-        let width = screen_width();
-        let height = screen_height();
-
-        spatial_hash.clear();
-
         // if is_mouse_button_down(MouseButton::Right) {
         //     let color = Color::new(
         //         rand::gen_range(0.0, 1.0),
@@ -316,46 +253,30 @@ async fn main() {
         //     colors.push(color);
         // }
 
-        for ball in balls.iter() {
-            spatial_hash.insert(ball.position, ball.id);
-
-            if display_state.display_mode == DisplayMode::Velocity {
-                if ball.velocity.length() > largest_speed {
-                    largest_speed = ball.velocity.length();
-                }
+        for ball in world.balls.iter() {
+            if display_state.display_mode == DisplayMode::Velocity
+                && ball.velocity.length() > largest_speed
+            {
+                largest_speed = ball.velocity.length();
             }
 
-            if display_state.display_mode == DisplayMode::Pressure {
-                if ball.pressure > largest_pressure {
-                    largest_pressure = ball.pressure;
-                }
+            if display_state.display_mode == DisplayMode::Pressure
+                && ball.pressure > largest_pressure
+            {
+                largest_pressure = ball.pressure;
             }
         }
 
-        for _ in 0..sim_steps {
-            for i in 0..balls.len() {
-                for &other_ball_id in spatial_hash.get_nearby_objects(balls[i].position, i).iter() {
-                    if i != other_ball_id {
-                        // Use index to get mutable references
-                        let (ball, other_ball) = if i < other_ball_id {
-                            let (left, right) = balls.split_at_mut(other_ball_id);
-                            (&mut left[i], &mut right[0])
-                        } else {
-                            let (left, right) = balls.split_at_mut(i);
-                            (&mut right[0], &mut left[other_ball_id])
-                        };
-
-                        if is_colliding(ball, other_ball) {
-                            resolve_collision(ball, other_ball, bounce_amount, max_pressure);
-                        } else {
-                            ball.pressure = 0.0;
-                            other_ball.pressure = 0.0;
-                        }
-                    }
-                }
-                resolve_boundaries(&mut balls[i], width, height, depth, bounce_amount);
-            }
-        }
+        let frame_time = get_frame_time();
+        largest_speed = speed_color_scale.update(largest_speed, frame_time);
+        largest_pressure = pressure_color_scale.update(largest_pressure, frame_time);
+
+        // A fixed scale keeps colours comparable across frames, auto-scaling uses the full range
+        let pressure_scale = if auto_pressure_scale {
+            largest_pressure
+        } else {
+            pressure_display_max
+        };
 
         let delta_time = get_frame_time();
         let mut rate = delta_time;
@@ -365,48 +286,51 @@ async fn main() {
         }
 
         if is_key_pressed(KeyCode::Space) {
-            do_gravity = !do_gravity
+            world.do_gravity = !world.do_gravity
         }
 
         if is_key_pressed(KeyCode::R) {
             display_state.toggle_display_mode();
         }
 
-        for ball in balls.iter_mut() {
-            // if is_mouse_button_down(MouseButton::Left); {
-            //     let mut force = mouse_position - ball.position;
+        if is_key_pressed(KeyCode::G) {
+            depth_fog_enabled = !depth_fog_enabled;
+        }
 
-            //     let distance = force.length();
-            //     if distance < 0.1 {
-            //         force /= distance;
-            //     }
+        world.step(rate);
 
-            //     let attraction_strength = gravity;
-            //     ball.velocity += force * attraction_strength * rate;
-            // }
+        // Fog spans the nearest to the farthest point of the box as seen from the camera
+        let box_center = vec3(width / 2.0, height / 2.0, depth / 2.0);
+        let box_reach = vec3(width, height, depth).length() / 2.0;
+        let center_distance = cam_pos.distance(box_center);
+        let (fog_near, fog_far) = (center_distance - box_reach, center_distance + box_reach);
 
-            if do_gravity {
-                ball.velocity.y += gravity;
+        for (key, mode) in DISPLAY_MODE_KEYS {
+            if is_key_pressed(key) {
+                display_state.set_display_mode(mode);
             }
+        }
 
-            match display_state.display_mode {
-                DisplayMode::Normal => ball.color = colors[ball.id],
-                DisplayMode::Velocity => {
-                    ball.color = get_color_from_vel(*ball, largest_speed);
-                }
-                DisplayMode::Pressure => {
-                    ball.color = get_color_from_pressure(*ball, largest_pressure);
-                }
-            }
-
-            ball.velocity.x *= resistance;
-            ball.velocity.y *= resistance;
-
-            ball.velocity = ball.velocity.clamp_length_max(max_speed);
-
-            ball.position += ball.velocity * rate;
-
-            draw_sphere(ball.position, ball.radius, None, ball.color)
+        for ball in world.balls.iter() {
+            let color = match display_state.display_mode {
+                DisplayMode::Normal => ball.color,
+                DisplayMode::Velocity => get_color_from_vel(*ball, largest_speed),
+                DisplayMode::Pressure => get_color_from_pressure(*ball, pressure_scale),
+            };
+
+            let color = if depth_fog_enabled {
+                depth_fog(
+                    color,
+                    background_color,
+                    ball.position.distance(cam_pos),
+                    fog_near,
+                    fog_far,
+                )
+            } else {
+                color
+            };
+
+            draw_sphere(ball.position, ball.radius, None, color)
         }
 
         // if is_key_down(KeyCode::F) {
@@ -446,34 +370,41 @@ async fn main() {
         draw_text(&format!("FPS: {:.2}", avg_fps), 10.0, 20.0, 30.0, WHITE);
 
         if auto_sim_steps {
-            if fps < target_fps {
-                sim_steps -= 1;
-            } else if fps > (target_fps + fps_boundary) {
-                sim_steps += 1;
-            }
-        } else {
-            if is_key_pressed(KeyCode::Up) {
-                sim_steps += 1;
-            } else if is_key_pressed(KeyCode::Down) {
-                sim_steps -= 1;
-            }
+            world.sim_steps = auto_steps.update(world.sim_steps, fps, get_frame_time());
+        } else if is_key_pressed(KeyCode::Up) {
+            world.sim_steps += 1;
+        } else if is_key_pressed(KeyCode::Down) {
+            world.sim_steps -= 1;
         }
 
-        sim_steps = sim_steps.clamp(1, 200);
+        world.sim_steps = world.sim_steps.clamp(MIN_SIM_STEPS, MAX_SIM_STEPS);
         // sim_steps = (sim_steps as f32 + 0.1 * (target_sim_steps as f32 - sim_steps as f32)) as i32;
 
         draw_text(
-            &format!("SIM STEPS: {}", sim_steps),
+            &format!("SIM STEPS: {}", world.sim_steps),
             10.0,
             50.0,
             30.0,
             WHITE,
         );
 
-        draw_text(&format!("BALLS: {}", balls.len()), 10.0, 80.0, 30.0, WHITE);
+        draw_text(
+            &format!("BALLS: {}", world.balls.len()),
+            10.0,
+            80.0,
+            30.0,
+            WHITE,
+        );
 
         set_default_camera();
 
+        match display_state.display_mode {
+            DisplayMode::Normal => {}
+            DisplayMode::Velocity => draw_legend("SPEED", largest_speed, velocity_gradient),
+            DisplayMode::Pressure => draw_legend("PRESSURE", pressure_scale, pressure_gradient),
+        }
+
+        frame_limiter.wait();
         next_frame().await
     }
 }
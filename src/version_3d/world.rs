@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+
+use glam::Vec3;
+use macroquad::prelude::Color;
+
+use crate::common::config::{Config, Wall};
+use crate::rng::Rng;
+use crate::spatial_hash_3d::SpatialHash;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ball {
+    pub id: usize,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub pressure: f32,
+    pub color: Color,
+    pub radius: f32,
+}
+
+// Balls closer than this have no usable direction between them
+const COINCIDENT_DISTANCE: f32 = 1e-6;
+
+fn is_colliding(ball: &Ball, otherball: &Ball) -> bool {
+    let dist = ball.position.distance(otherball.position);
+
+    dist < ball.radius + otherball.radius
+}
+
+/// Pressure a contact exerts on a ball: the magnitude of the pair's normal impulse spread over
+/// the ball's surface area
+fn contact_pressure(ball: &Ball, impulse: f32) -> f32 {
+    impulse / (4.0 * std::f32::consts::PI * ball.radius * ball.radius)
+}
+
+fn pair_key(id: usize, other_id: usize) -> (usize, usize) {
+    if id < other_id {
+        (id, other_id)
+    } else {
+        (other_id, id)
+    }
+}
+
+/// Mutable references to two different balls, in the order asked for
+fn pair_mut(balls: &mut [Ball], id: usize, other_id: usize) -> (&mut Ball, &mut Ball) {
+    if id < other_id {
+        let (left, right) = balls.split_at_mut(other_id);
+        (&mut left[id], &mut right[0])
+    } else {
+        let (left, right) = balls.split_at_mut(id);
+        (&mut right[0], &mut left[other_id])
+    }
+}
+
+/// Re-applies last frame's accumulated impulse so the solver starts near the resting solution
+fn warm_start(ball: &mut Ball, otherball: &mut Ball, impulse: f32) {
+    let normal = (otherball.position - ball.position).normalize_or_zero();
+
+    ball.velocity -= normal * impulse;
+    otherball.velocity += normal * impulse;
+
+    ball.pressure += contact_pressure(ball, impulse);
+    otherball.pressure += contact_pressure(otherball, impulse);
+}
+
+fn resolve_collision(
+    ball: &mut Ball,
+    otherball: &mut Ball,
+    bounce_amount: f32,
+    accumulated_impulse: &mut f32,
+) {
+    let dist = ball.position.distance(otherball.position);
+
+    let overlap = (ball.radius + otherball.radius) - dist;
+
+    if overlap < 0.001 {
+        return;
+    }
+
+    // Balls on top of each other are pushed apart along +x rather than dividing by zero
+    let pdiff = if dist < COINCIDENT_DISTANCE {
+        Vec3::X
+    } else {
+        (otherball.position - ball.position) / dist
+    };
+
+    ball.position -= pdiff * overlap / 2.0;
+    otherball.position += pdiff * overlap / 2.0;
+
+    let vdiff = otherball.velocity - ball.velocity;
+
+    let dot_product = vdiff.dot(pdiff);
+
+    // Clamp the accumulated impulse rather than each increment, so a later iteration can
+    // take back an overshoot but the pair is never pulled together
+    let old_impulse = *accumulated_impulse;
+    *accumulated_impulse = (old_impulse - dot_product * bounce_amount).max(0.0);
+
+    let force = old_impulse - *accumulated_impulse;
+
+    // Summed over every iteration and contact this adds up to each pair's final accumulated impulse
+    ball.pressure += contact_pressure(ball, -force);
+    otherball.pressure += contact_pressure(otherball, -force);
+
+    ball.velocity += force * pdiff;
+    otherball.velocity -= force * pdiff;
+}
+
+fn resolve_boundaries(ball: &mut Ball, size: Vec3, walls: &[Wall; 6]) {
+    let [left, right, top, bottom, front, back] = walls;
+
+    if ball.position.x - ball.radius < 0.0 && left.blocks(ball.velocity.x <= 0.0) {
+        ball.position.x = ball.radius;
+        if ball.velocity.x < 0.0 {
+            ball.velocity.x *= -left.restitution;
+        }
+    } else if ball.position.x + ball.radius > size.x && right.blocks(ball.velocity.x >= 0.0) {
+        ball.position.x = size.x - ball.radius;
+        if ball.velocity.x > 0.0 {
+            ball.velocity.x *= -right.restitution;
+        }
+    }
+
+    if ball.position.y - ball.radius < 0.0 && top.blocks(ball.velocity.y <= 0.0) {
+        ball.position.y = ball.radius;
+        if ball.velocity.y < 0.0 {
+            ball.velocity.y *= -top.restitution;
+        }
+    } else if ball.position.y + ball.radius > size.y && bottom.blocks(ball.velocity.y >= 0.0) {
+        ball.position.y = size.y - ball.radius;
+        if ball.velocity.y > 0.0 {
+            ball.velocity.y *= -bottom.restitution;
+        }
+    }
+
+    if ball.position.z - ball.radius < 0.0 && front.blocks(ball.velocity.z <= 0.0) {
+        ball.position.z = ball.radius;
+        if ball.velocity.z < 0.0 {
+            ball.velocity.z *= -front.restitution;
+        }
+    } else if ball.position.z + ball.radius > size.z && back.blocks(ball.velocity.z >= 0.0) {
+        ball.position.z = size.z - ball.radius;
+        if ball.velocity.z > 0.0 {
+            ball.velocity.z *= -back.restitution;
+        }
+    }
+}
+
+fn random_in(rng: &mut Rng, min: Vec3, max: Vec3) -> Vec3 {
+    Vec3::new(
+        min.x + (max.x - min.x) * rng.next_f32(),
+        min.y + (max.y - min.y) * rng.next_f32(),
+        min.z + (max.z - min.z) * rng.next_f32(),
+    )
+}
+
+/// The 3D ball simulation, without any windowing, input or rendering
+pub struct World3D {
+    pub balls: Vec<Ball>,
+    pub width: f32,
+    pub height: f32,
+    pub depth: f32,
+    /// Downward acceleration in units/s²
+    pub gravity: f32,
+    pub do_gravity: bool,
+    /// Fraction of the sideways and vertical velocity kept each step
+    pub resistance: f32,
+    pub bounce_amount: f32,
+    pub max_speed: f32,
+    pub max_pressure: f32,
+    pub sim_steps: i32,
+    /// Left, right, top, bottom, front and back
+    pub walls: [Wall; 6],
+    /// Source of every random choice the world makes, seeded from `seed` in the config
+    pub rng: Rng,
+    spatial_hash: SpatialHash<usize>,
+    // Ordered by pair so warm starting applies impulses in the same order on every toolchain
+    contact_impulses: BTreeMap<(usize, usize), f32>,
+    sim_time: f64,
+}
+
+impl World3D {
+    /// Creates a world of `ball_count_3d` balls scattered through a `width` x `height` x `depth`
+    /// box
+    pub fn new(config: &Config) -> Self {
+        let ball_radius = config.ball_radius;
+        let size = Vec3::new(config.width, config.height, config.depth);
+        let mut rng = Rng::new(config.seed);
+
+        let balls = (0..config.ball_count_3d)
+            .map(|id| Ball {
+                id,
+                position: random_in(&mut rng, Vec3::splat(ball_radius), size - ball_radius),
+                velocity: random_in(&mut rng, Vec3::splat(-100.0), Vec3::splat(100.0)),
+                pressure: 0.0,
+                color: Color::new(rng.next_f32(), rng.next_f32(), rng.next_f32(), 1.0),
+                radius: ball_radius,
+            })
+            .collect();
+
+        Self {
+            balls,
+            width: config.width,
+            height: config.height,
+            depth: config.depth,
+            gravity: config.gravity,
+            do_gravity: true,
+            resistance: config.resistance,
+            bounce_amount: config.bounce_amount,
+            max_speed: config.max_speed,
+            max_pressure: config.max_pressure,
+            sim_steps: config.sim_steps,
+            walls: config.walls_3d,
+            rng,
+            spatial_hash: SpatialHash::new((ball_radius * 2.0) + 2.0),
+            contact_impulses: BTreeMap::new(),
+            sim_time: 0.0,
+        }
+    }
+
+    pub fn bodies(&self) -> &[Ball] {
+        &self.balls
+    }
+
+    /// Resolves the current contacts over `sim_steps` iterations, then moves every ball on by `dt`
+    pub fn step(&mut self, dt: f32) {
+        self.solve();
+        self.integrate(dt);
+        self.sim_time += dt as f64;
+    }
+
+    /// Seconds of simulated time stepped since the world was made or last `reset`
+    pub fn time(&self) -> f64 {
+        self.sim_time
+    }
+
+    /// Starts the clock over at zero
+    pub fn reset(&mut self) {
+        self.sim_time = 0.0;
+    }
+
+    fn solve(&mut self) {
+        let balls = &mut self.balls;
+        let size = Vec3::new(self.width, self.height, self.depth);
+
+        self.spatial_hash.clear();
+        for ball in balls.iter() {
+            self.spatial_hash.insert(ball.position, ball.id);
+        }
+
+        // Pressure is rebuilt from this step's contacts, so a ball only loses it by losing contact
+        for ball in balls.iter_mut() {
+            ball.pressure = 0.0;
+        }
+
+        for ((id, other_id), impulse) in std::mem::take(&mut self.contact_impulses) {
+            let (ball, other_ball) = pair_mut(balls, id, other_id);
+
+            if is_colliding(ball, other_ball) {
+                warm_start(ball, other_ball, impulse);
+                self.contact_impulses.insert((id, other_id), impulse);
+            }
+        }
+
+        for _ in 0..self.sim_steps {
+            for i in 0..balls.len() {
+                for other_id in self.spatial_hash.get_nearby_objects(balls[i].position, i) {
+                    let (ball, other_ball) = pair_mut(balls, i, other_id);
+
+                    if is_colliding(ball, other_ball) {
+                        let accumulated_impulse = self
+                            .contact_impulses
+                            .entry(pair_key(i, other_id))
+                            .or_insert(0.0);
+
+                        resolve_collision(
+                            ball,
+                            other_ball,
+                            self.bounce_amount,
+                            accumulated_impulse,
+                        );
+                    }
+                }
+                resolve_boundaries(&mut balls[i], size, &self.walls);
+            }
+        }
+
+        self.contact_impulses.retain(|_, impulse| *impulse > 0.0);
+
+        for ball in balls.iter_mut() {
+            ball.pressure = ball.pressure.clamp(0.0, self.max_pressure);
+        }
+    }
+
+    fn integrate(&mut self, dt: f32) {
+        for ball in self.balls.iter_mut() {
+            if self.do_gravity {
+                ball.velocity.y += self.gravity * dt;
+            }
+
+            ball.velocity.x *= self.resistance;
+            ball.velocity.y *= self.resistance;
+
+            ball.velocity = ball.velocity.clamp_length_max(self.max_speed);
+
+            ball.position += ball.velocity * dt;
+        }
+    }
+
+    /// Total kinetic energy of every ball, treating each as unit mass
+    pub fn kinetic_energy(&self) -> f32 {
+        self.balls
+            .iter()
+            .map(|ball| 0.5 * ball.velocity.length_squared())
+            .sum()
+    }
+}
@@ -0,0 +1,40 @@
+use rust_physics_engine::common::auto_sim_steps::{AutoSimSteps, MAX_SIM_STEPS, MIN_SIM_STEPS};
+
+const DT: f32 = 1.0 / 60.0;
+
+#[test]
+fn steps_follow_the_frame_rate_within_bounds() {
+    let mut auto_steps = AutoSimSteps::new(60, 20);
+
+    assert_eq!(auto_steps.update(10, 50, DT), 9);
+    assert_eq!(auto_steps.update(10, 70, DT), 10);
+    assert_eq!(auto_steps.update(10, 90, DT), 11);
+    assert_eq!(auto_steps.update(MAX_SIM_STEPS, 200, DT), MAX_SIM_STEPS);
+    assert_eq!(auto_steps.update(MIN_SIM_STEPS, 10, DT), MIN_SIM_STEPS);
+}
+
+#[test]
+fn warns_once_a_while_when_pinned_at_the_floor() {
+    let mut auto_steps = AutoSimSteps::new(60, 20);
+    let mut sim_steps = 20;
+
+    // Twenty seconds too slow to keep up, then recovering
+    for _ in 0..20 * 60 {
+        sim_steps = auto_steps.update(sim_steps, 30, DT);
+    }
+    assert_eq!(sim_steps, MIN_SIM_STEPS);
+    assert_eq!(auto_steps.warnings(), 1);
+
+    for _ in 0..2 * 60 {
+        sim_steps = auto_steps.update(sim_steps, 100, DT);
+    }
+    assert!(sim_steps > MIN_SIM_STEPS);
+    assert_eq!(auto_steps.warnings(), 1);
+
+    // A brief dip back to the floor isn't worth a warning
+    let mut auto_steps = AutoSimSteps::new(60, 20);
+    for _ in 0..60 {
+        auto_steps.update(MIN_SIM_STEPS, 30, DT);
+    }
+    assert_eq!(auto_steps.warnings(), 0);
+}
@@ -0,0 +1,22 @@
+use rust_physics_engine::common::color_scale::ColorScale;
+
+#[test]
+fn scale_rises_at_once_and_decays_smoothly() {
+    let mut scale = ColorScale::new(0.1);
+
+    assert_eq!(scale.update(10.0, 0.5), 10.0);
+
+    // Half a second at a decay of 0.1 a second keeps about a third of it
+    let eased = scale.update(1.0, 0.5);
+    assert!((eased - 10.0 * 0.1_f32.sqrt()).abs() < 1e-4, "{}", eased);
+
+    // A full second of steps lands on the same value as one step of a second
+    let mut stepped = ColorScale::new(0.1);
+    stepped.update(10.0, 0.0);
+    for _ in 0..60 {
+        stepped.update(0.0, 1.0 / 60.0);
+    }
+    assert!((stepped.max - 1.0).abs() < 1e-3, "{}", stepped.max);
+
+    assert_eq!(ColorScale::new(0.0).update(3.0, 0.016), 3.0);
+}
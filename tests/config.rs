@@ -0,0 +1,95 @@
+use rust_physics_engine::common::config::{
+    load_config, load_config_or_default, load_profiles, load_profiles_or_default, Config,
+    BASE_PROFILE, CONFIG_VERSION,
+};
+
+#[test]
+fn missing_config_falls_back_to_defaults() {
+    let config = load_config_or_default("does_not_exist.toml");
+
+    assert!(config.ball_count_2d > 0);
+}
+
+// The whole config.toml from before versioning, with none of the keys added since
+const LEGACY_CONFIG: &str = "\
+ball_count_2d = 1000
+ball_count_3d = 500
+ball_radius = 10.0
+gravity = 9.81
+resistance = 0.999
+bounce_amount = 0.6
+max_speed = 2000.0
+max_pressure = 0.1
+width = 1200.0
+height = 800.0
+depth = 600.0
+auto_sim_steps = true
+sim_steps = 1
+target_fps = 60
+fps_boundary = 20
+delete_dist = 20.0
+";
+
+#[test]
+fn unversioned_config_scales_per_frame_gravity() {
+    let path = std::env::temp_dir().join("rust_physics_engine_legacy_config.toml");
+    std::fs::write(&path, LEGACY_CONFIG).expect("Failed to write legacy config");
+
+    let config = load_config(path.to_str().unwrap());
+
+    assert_eq!(config.config_version, CONFIG_VERSION);
+    assert!((config.gravity - 588.6).abs() < 1e-3, "{}", config.gravity);
+    // Keys the old file never had come from the defaults
+    assert_eq!(config.ball_count_2d, 1000);
+    assert_eq!(config.seed, Config::default().seed);
+}
+
+#[test]
+fn profiles_override_only_their_own_keys() {
+    let profiles = load_profiles_or_default("does_not_exist.toml");
+    let base = &profiles[BASE_PROFILE];
+    let bouncy = &profiles["bouncy"];
+
+    assert_eq!(bouncy.bounce_amount, 0.95);
+    assert_eq!(bouncy.gravity, base.gravity);
+    assert_eq!(bouncy.ball_count_2d, base.ball_count_2d);
+}
+
+#[test]
+fn unversioned_config_without_gravity_keeps_the_default_gravity() {
+    let path = std::env::temp_dir().join("rust_physics_engine_no_gravity_config.toml");
+    std::fs::write(&path, "ball_count_2d = 100\n").expect("Failed to write config");
+
+    let config = load_config(path.to_str().unwrap());
+
+    // The defaults are already in units/s², only the file's own keys are migrated
+    assert_eq!(config.gravity, Config::default().gravity);
+    assert_eq!(config.ball_count_2d, 100);
+}
+
+#[test]
+fn outdated_profiles_only_migrate_their_own_gravity() {
+    let path = std::env::temp_dir().join("rust_physics_engine_outdated_profile_config.toml");
+    std::fs::write(
+        &path,
+        "\
+config_version = 2
+gravity = 500.0
+
+[profiles.inherits]
+config_version = 1
+bounce_amount = 0.9
+
+[profiles.overrides]
+config_version = 1
+gravity = 10.0
+",
+    )
+    .expect("Failed to write config");
+
+    let profiles = load_profiles(path.to_str().unwrap());
+
+    assert_eq!(profiles[BASE_PROFILE].gravity, 500.0);
+    assert_eq!(profiles["inherits"].gravity, 500.0);
+    assert_eq!(profiles["overrides"].gravity, 600.0);
+}
@@ -0,0 +1,28 @@
+use std::time::{Duration, Instant};
+
+use rust_physics_engine::common::frame_limiter::FrameLimiter;
+
+#[test]
+fn frames_take_at_least_the_budget() {
+    let mut limiter = FrameLimiter::new(Some(100));
+    let start = Instant::now();
+
+    // The first call only starts the clock
+    for _ in 0..6 {
+        limiter.wait();
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn no_cap_never_sleeps() {
+    let mut limiter = FrameLimiter::new(None);
+    let start = Instant::now();
+
+    for _ in 0..1000 {
+        limiter.wait();
+    }
+
+    assert!(start.elapsed() < Duration::from_millis(50));
+}
@@ -0,0 +1,136 @@
+use rust_physics_engine::narrow_phase::{collide, collide_circles, intersects};
+use rust_physics_engine::scalar::{consts, vector2 as vec2, Scalar, Vector2 as Vec2};
+use rust_physics_engine::shape::{Aabb, Circle, ConvexPolygon, Transform};
+
+fn assert_close(a: Vec2, b: Vec2, tolerance: Scalar) {
+    assert!(a.distance(b) < tolerance, "{} != {}", a, b);
+}
+
+#[test]
+fn boxes_collide_along_the_shallowest_axis() {
+    let rect = Aabb {
+        half_extents: vec2(2.0, 1.0),
+    };
+
+    let contact = collide(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(vec2(3.0, 0.5)),
+    )
+    .expect("boxes overlap");
+    assert_close(contact.normal, vec2(1.0, 0.0), 1e-4);
+    assert!((contact.depth - 1.0).abs() < 1e-4, "{}", contact.depth);
+
+    assert!(!intersects(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(vec2(5.0, 0.0)),
+    ));
+}
+
+#[test]
+fn gjk_matches_the_closed_form_for_circles() {
+    let circle = Circle { radius: 1.0 };
+    let (a, b) = (vec2(0.0, 0.0), vec2(1.2, 0.9));
+
+    let exact = collide_circles(a, 1.0, b, 1.0).expect("circles overlap");
+    let general =
+        collide(&circle, &Transform::at(a), &circle, &Transform::at(b)).expect("circles overlap");
+
+    assert_close(general.normal, exact.normal, 1e-2);
+    assert!((general.depth - exact.depth).abs() < 1e-2);
+    let stacked = collide_circles(a, 1.0, a, 1.0).expect("circles overlap");
+    assert_eq!(stacked.normal, vec2(1.0, 0.0));
+    assert_eq!(stacked.depth, 2.0);
+
+    assert!(collide(
+        &circle,
+        &Transform::at(a),
+        &circle,
+        &Transform::at(vec2(2.5, 0.0))
+    )
+    .is_none());
+}
+
+#[test]
+fn mixed_shapes_share_one_path() {
+    let triangle = ConvexPolygon {
+        vertices: vec![vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(0.0, 1.0)],
+    };
+    let circle = Circle { radius: 0.5 };
+
+    // The circle rests 0.25 into the triangle's flat bottom
+    let contact = collide(
+        &triangle,
+        &Transform::at(vec2(0.0, 0.0)),
+        &circle,
+        &Transform::at(vec2(0.0, -1.25)),
+    )
+    .expect("shapes overlap");
+    assert_close(contact.normal, vec2(0.0, -1.0), 1e-3);
+    assert!((contact.depth - 0.25).abs() < 1e-3, "{}", contact.depth);
+
+    // A box turned 45 degrees reaches further along the axes
+    let rect = Aabb {
+        half_extents: vec2(1.0, 1.0),
+    };
+    let turned = Transform {
+        position: vec2(2.3, 0.0),
+        rotation: consts::FRAC_PI_4,
+    };
+    assert!(intersects(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &turned
+    ));
+    assert!(!intersects(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(turned.position)
+    ));
+}
+
+#[test]
+fn shapes_lined_up_on_an_axis_still_overlap() {
+    let circle = Circle { radius: 1.0 };
+    let contact = collide(
+        &circle,
+        &Transform::at(vec2(0.0, 0.0)),
+        &circle,
+        &Transform::at(vec2(1.0, 0.0)),
+    )
+    .expect("circles overlap head-on");
+    assert_close(contact.normal, vec2(1.0, 0.0), 1e-2);
+    assert!((contact.depth - 1.0).abs() < 1e-2, "{}", contact.depth);
+
+    let rect = Aabb {
+        half_extents: vec2(1.0, 1.0),
+    };
+    let contact = collide(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(vec2(0.0, 1.0)),
+    )
+    .expect("boxes overlap head-on");
+    assert_close(contact.normal, vec2(0.0, 1.0), 1e-4);
+    assert!((contact.depth - 1.0).abs() < 1e-4, "{}", contact.depth);
+    assert!(intersects(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(vec2(1.0, 0.0)),
+    ));
+
+    // Edge to edge is only touching
+    assert!(!intersects(
+        &rect,
+        &Transform::at(vec2(0.0, 0.0)),
+        &rect,
+        &Transform::at(vec2(2.0, 0.0)),
+    ));
+}
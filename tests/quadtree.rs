@@ -0,0 +1,50 @@
+use rust_physics_engine::quadtree::QuadTree;
+use rust_physics_engine::rng::Rng;
+use rust_physics_engine::scalar::vector2 as vec2;
+use rust_physics_engine::spatial_hash::SpatialHash;
+
+fn sorted(pairs: impl Iterator<Item = (usize, usize)>) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<_> = pairs.map(|(a, b)| (a.min(b), a.max(b))).collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+#[test]
+fn candidate_pairs_match_within_reach() {
+    let mut tree = QuadTree::new(vec2(0.0, 0.0), vec2(100.0, 100.0), 10.0, 1, 8);
+
+    tree.insert(vec2(5.0, 5.0), 0);
+    tree.insert(vec2(14.0, 5.0), 1);
+    tree.insert(vec2(6.0, 6.0), 2);
+    tree.insert(vec2(45.0, 45.0), 3);
+    // Outside the bounds, but still within reach of 3
+    tree.insert(vec2(50.0, 52.0), 4);
+    tree.insert(vec2(150.0, 150.0), 5);
+
+    assert_eq!(
+        sorted(tree.candidate_pairs()),
+        vec![(0, 1), (0, 2), (1, 2), (3, 4)]
+    );
+}
+
+#[test]
+fn clustered_pairs_are_a_subset_of_the_hash_pairs() {
+    let mut rng = Rng::new(7);
+    let positions: Vec<_> = (0..500)
+        .map(|id| (id, vec2(rng.gen_range(0.0, 60.0), rng.gen_range(0.0, 60.0))))
+        .collect();
+
+    let mut tree = QuadTree::new(vec2(0.0, 0.0), vec2(1000.0, 1000.0), 10.0, 8, 10);
+    tree.update_all(&positions);
+    let mut hash = SpatialHash::new(10.0);
+    hash.update_all(&positions);
+
+    let tree_pairs = sorted(tree.candidate_pairs());
+    let hash_pairs = sorted(hash.candidate_pairs());
+
+    assert!(tree.leaf_count() > 1);
+    assert!(!tree_pairs.is_empty());
+    assert!(tree_pairs
+        .iter()
+        .all(|pair| hash_pairs.binary_search(pair).is_ok()));
+}
@@ -0,0 +1,43 @@
+use rust_physics_engine::scalar::{consts, vector2 as vec2, Scalar};
+use rust_physics_engine::shape::{Aabb, Circle, Shape, Transform};
+
+fn assert_close(a: Scalar, b: Scalar) {
+    assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+}
+
+#[test]
+fn circle_properties() {
+    let circle = Circle { radius: 2.0 };
+
+    assert_close(circle.area(), 4.0 * consts::PI);
+    assert_close(circle.moment_of_inertia(3.0), 6.0);
+    assert_eq!(
+        circle.aabb(&Transform::at(vec2(10.0, 5.0))),
+        (vec2(8.0, 3.0), vec2(12.0, 7.0))
+    );
+    assert_eq!(circle.support(vec2(0.0, -5.0)), vec2(0.0, -2.0));
+}
+
+#[test]
+fn box_properties() {
+    let rect = Aabb {
+        half_extents: vec2(2.0, 1.0),
+    };
+
+    assert_close(rect.area(), 8.0);
+    // m(w² + h²)/12 with w = 4, h = 2
+    assert_close(rect.moment_of_inertia(3.0), 5.0);
+    assert_eq!(rect.support(vec2(-1.0, 0.5)), vec2(-2.0, 1.0));
+    assert_eq!(
+        rect.aabb(&Transform::at(vec2(0.0, 0.0))),
+        (vec2(-2.0, -1.0), vec2(2.0, 1.0))
+    );
+
+    // A quarter turn swaps the extents
+    let (min, max) = rect.aabb(&Transform {
+        position: vec2(0.0, 0.0),
+        rotation: consts::FRAC_PI_2,
+    });
+    assert_close(min.x, -1.0);
+    assert_close(max.y, 2.0);
+}
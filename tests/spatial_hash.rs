@@ -0,0 +1,319 @@
+use rust_physics_engine::scalar::vector2 as vec2;
+
+use rust_physics_engine::spatial_hash::SpatialHash;
+use rust_physics_engine::spatial_hash_3d::SpatialHash as SpatialHash3D;
+
+#[test]
+fn candidate_pairs_cover_adjacent_cells_once() {
+    let mut hash = SpatialHash::new(10.0);
+
+    hash.insert(vec2(5.0, 5.0), 0);
+    hash.insert(vec2(15.0, 5.0), 1);
+    hash.insert(vec2(6.0, 6.0), 2);
+    hash.insert(vec2(45.0, 45.0), 3);
+
+    let mut pairs: Vec<(usize, usize)> = hash
+        .candidate_pairs()
+        .map(|(a, b)| (a.min(b), a.max(b)))
+        .collect();
+    pairs.sort_unstable();
+
+    assert_eq!(pairs, vec![(0, 1), (0, 2), (1, 2)]);
+}
+
+#[test]
+fn occupancy_ratio_counts_cells_per_object() {
+    let mut hash = SpatialHash::new(10.0);
+
+    hash.insert(vec2(1.0, 1.0), 0);
+    hash.insert(vec2(2.0, 2.0), 1);
+    hash.insert(vec2(25.0, 25.0), 2);
+    hash.insert(vec2(45.0, 45.0), 3);
+
+    assert_eq!(hash.occupied_cells(), 3);
+    assert_eq!(hash.occupancy_ratio(), 0.75);
+}
+
+// Deliberately not `Copy`, like a generational handle
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Handle(u32, u32);
+
+#[test]
+fn stores_non_copy_handles() {
+    let mut hash = SpatialHash::new(10.0);
+
+    hash.insert(vec2(5.0, 5.0), Handle(0, 1));
+    hash.insert(vec2(8.0, 5.0), Handle(1, 1));
+    hash.update(Handle(1, 1), vec2(55.0, 5.0));
+    hash.insert(vec2(12.0, 5.0), Handle(2, 3));
+
+    assert_eq!(
+        hash.get_nearby_objects(vec2(5.0, 5.0), Handle(0, 1)),
+        vec![Handle(2, 3)]
+    );
+    assert_eq!(
+        hash.candidate_pairs().collect::<Vec<_>>(),
+        vec![(Handle(0, 1), Handle(2, 3))]
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "inserted into the spatial hash twice")]
+fn duplicate_insert_panics_in_debug() {
+    let mut hash = SpatialHash::new(10.0);
+
+    hash.insert(vec2(5.0, 5.0), 0);
+    hash.insert(vec2(35.0, 5.0), 0);
+}
+
+#[test]
+fn nearby_objects_are_listed_once() {
+    let mut hash = SpatialHash::new(10.0);
+
+    // Spread over all nine cells around the query, several to a cell
+    let mut id = 0;
+    for x in [-5.0, 5.0, 15.0] {
+        for y in [-5.0, 5.0, 15.0] {
+            for offset in [0.0, 1.0, 2.0] {
+                hash.insert(vec2(x + offset, y + offset), id);
+                id += 1;
+            }
+        }
+    }
+    hash.update(0, vec2(6.0, 6.0));
+
+    let mut nearby = hash.get_nearby_objects(vec2(5.0, 5.0), 4);
+    let count = nearby.len();
+    nearby.sort_unstable();
+    nearby.dedup();
+
+    assert_eq!(count, id - 1);
+    assert_eq!(nearby.len(), count);
+}
+
+#[test]
+#[should_panic(expected = "cell_size must be positive")]
+fn non_positive_cell_size_panics() {
+    let mut hash = SpatialHash::<usize>::new(10.0);
+    hash.set_cell_size(0.0);
+}
+
+#[test]
+fn point_queries_in_3d_include_every_nearby_object() {
+    let mut hash = SpatialHash3D::new(10.0);
+
+    hash.insert(glam::vec3(5.0, 5.0, 5.0), 0);
+    hash.insert(glam::vec3(15.0, 5.0, 5.0), 1);
+    hash.insert(glam::vec3(45.0, 45.0, 45.0), 2);
+
+    let mut at_point = hash.get_nearby_objects_at(glam::vec3(8.0, 8.0, 8.0));
+    at_point.sort_unstable();
+
+    assert_eq!(at_point, vec![0, 1]);
+    assert_eq!(
+        hash.get_nearby_objects(glam::vec3(5.0, 5.0, 5.0), 0),
+        vec![1]
+    );
+}
+
+#[test]
+fn circular_queries_skip_corner_cells() {
+    let mut hash = SpatialHash::new(10.0);
+
+    // Query from the middle of cell (0, 0) with a range of two and a half cells
+    hash.insert(vec2(5.0, 5.0), 0);
+    hash.insert(vec2(25.0, 5.0), 1);
+    hash.insert(vec2(5.0, -15.0), 2);
+    // In a corner cell of the 5x5 square, whose nearest point is about 21 away
+    hash.insert(vec2(25.0, 25.0), 3);
+    // Beyond the square altogether
+    hash.insert(vec2(45.0, 5.0), 4);
+
+    let mut nearby = hash.get_nearby_objects_circular(vec2(5.0, 5.0), 25.0);
+    nearby.sort_unstable();
+    assert_eq!(nearby, vec![0, 1, 2, 3]);
+
+    let mut nearby = hash.get_nearby_objects_circular(vec2(5.0, 5.0), 20.0);
+    nearby.sort_unstable();
+    assert_eq!(nearby, vec![0, 1, 2]);
+}
+
+#[test]
+fn anisotropic_cells_reach_further_along_the_wide_axis() {
+    // Cells 40 wide and 10 tall, so 70 apart across is adjacent but 20 apart down is not
+    let mut hash = SpatialHash::new_anisotropic(40.0, 10.0);
+    hash.insert(vec2(5.0, 5.0), 0);
+    hash.insert(vec2(75.0, 5.0), 1);
+    hash.insert(vec2(5.0, 25.0), 2);
+
+    let pairs: Vec<_> = hash.candidate_pairs().collect();
+    assert_eq!(pairs.len(), 1);
+    assert!(pairs.contains(&(0, 1)) || pairs.contains(&(1, 0)));
+    assert_eq!(hash.cell_size(), 10.0);
+
+    let mut hash_3d = SpatialHash3D::new_anisotropic(40.0, 10.0, 10.0);
+    hash_3d.insert(glam::vec3(75.0, 5.0, 5.0), 1);
+    hash_3d.insert(glam::vec3(5.0, 25.0, 5.0), 2);
+    assert_eq!(
+        hash_3d.get_nearby_objects_at(glam::vec3(5.0, 5.0, 5.0)),
+        vec![1]
+    );
+}
+
+#[test]
+fn nearest_looks_past_the_first_ring_with_a_hit() {
+    let positions = [
+        vec2(10.1, 5.0),
+        vec2(5.5, 5.0),
+        vec2(19.9, 19.9),
+        vec2(5.0, 24.0),
+    ];
+    let position_of = |id: &usize| positions[*id];
+
+    let mut hash = SpatialHash::new(10.0);
+    assert_eq!(hash.nearest(vec2(5.0, 5.0), position_of), None);
+
+    // Across a cell boundary beats a point further away in the same cell
+    hash.insert(positions[0], 0);
+    hash.insert(positions[1], 1);
+    assert_eq!(hash.nearest(vec2(9.9, 5.0), position_of), Some(0));
+    assert_eq!(hash.nearest(vec2(5.0, 5.0), position_of), Some(1));
+
+    // The corner of the first ring is about 21 away, the middle of the second only 19
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(positions[2], 2);
+    hash.insert(positions[3], 3);
+    assert_eq!(hash.nearest(vec2(5.0, 5.0), position_of), Some(3));
+
+    // Far outside every occupied cell still finds something
+    assert_eq!(hash.nearest(vec2(500.0, -300.0), position_of), Some(2));
+}
+
+#[test]
+fn nearest_only_searches_the_occupied_cells() {
+    let positions = [vec2(0.0, 0.0), vec2(30.0, 0.0)];
+    let position_of = |id: &usize| positions[*id];
+
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(positions[0], 0);
+    hash.insert(positions[1], 1);
+
+    // A million cells away, which ring by ring would never finish
+    assert_eq!(hash.nearest(vec2(1.0e7, 0.0), position_of), Some(1));
+    assert_eq!(hash.nearest(vec2(-1.0e7, 1.0e7), position_of), Some(0));
+
+    // Removed objects are never found, even though the searched cells don't shrink
+    hash.remove_by_id(1);
+    assert_eq!(hash.nearest(vec2(1.0e7, 0.0), position_of), Some(0));
+    hash.remove_by_id(0);
+    assert_eq!(hash.nearest(vec2(1.0e7, 0.0), position_of), None);
+}
+
+#[test]
+fn update_all_only_moves_objects_that_crossed_cells() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.update_all(&[
+        (0, vec2(1.0, 1.0)),
+        (1, vec2(5.0, 5.0)),
+        (2, vec2(25.0, 5.0)),
+    ]);
+
+    // 0 and 2 shuffle around inside their cells, only 1 crosses over
+    let moved = hash.update_all(&[
+        (0, vec2(9.0, 9.0)),
+        (1, vec2(15.0, 5.0)),
+        (2, vec2(21.0, 1.0)),
+    ]);
+    assert_eq!(moved, 1);
+    assert_eq!(hash.get_nearby_objects(vec2(25.0, 5.0), 2), vec![1]);
+
+    // New objects count as moved in
+    assert_eq!(
+        hash.update_all(&[(0, vec2(9.0, 9.0)), (3, vec2(45.0, 45.0))]),
+        1
+    );
+}
+
+#[test]
+fn removing_shrinks_the_cell_and_drops_it_once_empty() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(vec2(1.0, 1.0), 0);
+    hash.insert(vec2(2.0, 2.0), 1);
+    hash.insert(vec2(3.0, 3.0), 2);
+    hash.insert(vec2(45.0, 45.0), 3);
+
+    hash.remove_by_id(1);
+    let mut nearby = hash.get_nearby_objects(vec2(1.0, 1.0), 0);
+    nearby.sort_unstable();
+    assert_eq!(nearby, vec![2]);
+    assert_eq!(hash.occupied_cells(), 2);
+
+    hash.remove_by_id(0);
+    hash.remove_by_id(2);
+    assert_eq!(hash.occupied_cells(), 1);
+    assert_eq!(hash.candidate_pairs().count(), 0);
+}
+
+#[test]
+fn removing_an_unknown_id_does_nothing() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(vec2(1.0, 1.0), 0);
+    hash.insert(vec2(2.0, 2.0), 1);
+
+    hash.remove_by_id(7);
+    // Already removed
+    hash.remove_by_id(1);
+    hash.remove_by_id(1);
+
+    assert_eq!(hash.occupied_cells(), 1);
+    assert_eq!(hash.get_nearby_objects(vec2(1.0, 1.0), 7), vec![0]);
+}
+
+#[test]
+fn updating_within_a_cell_leaves_the_hash_alone() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(vec2(1.0, 1.0), 0);
+    hash.insert(vec2(2.0, 2.0), 1);
+
+    assert!(!hash.update(0, vec2(9.0, 9.0)));
+    assert_eq!(hash.occupied_cells(), 1);
+    assert_eq!(hash.get_nearby_objects(vec2(9.0, 9.0), 0), vec![1]);
+    // Reinserting would have put 0 after 1 in the cell
+    assert_eq!(hash.candidate_pairs().collect::<Vec<_>>(), vec![(0, 1)]);
+}
+
+#[test]
+fn updating_across_cells_moves_the_object() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(vec2(1.0, 1.0), 0);
+    hash.insert(vec2(2.0, 2.0), 1);
+    hash.insert(vec2(55.0, 55.0), 2);
+
+    assert!(hash.update(0, vec2(52.0, 52.0)));
+    assert_eq!(hash.occupied_cells(), 2);
+    assert_eq!(
+        hash.get_nearby_objects(vec2(2.0, 2.0), 1),
+        Vec::<usize>::new()
+    );
+    assert_eq!(hash.get_nearby_objects(vec2(52.0, 52.0), 0), vec![2]);
+
+    // Not stored yet, so it gets inserted
+    assert!(hash.update(3, vec2(25.0, 25.0)));
+    assert_eq!(hash.occupied_cells(), 3);
+}
+
+#[test]
+fn candidate_pairs_come_out_in_cell_order() {
+    let mut hash = SpatialHash::new(10.0);
+    hash.insert(vec2(25.0, 5.0), 0);
+    hash.insert(vec2(5.0, 5.0), 1);
+    hash.insert(vec2(15.0, 5.0), 2);
+    hash.insert(vec2(5.0, 15.0), 3);
+
+    // Cells by x then y, whatever order they were filled in or the hash map keeps them in
+    assert_eq!(
+        hash.candidate_pairs().collect::<Vec<_>>(),
+        vec![(1, 2), (1, 3), (3, 2), (2, 0)]
+    );
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "macroquad")]
+
+use rust_physics_engine::common::config::Config;
+use rust_physics_engine::version_3d::world::World3D;
+
+const DT: f32 = 1.0 / 60.0;
+
+fn test_config() -> Config {
+    Config {
+        ball_count_3d: 300,
+        width: 400.0,
+        height: 400.0,
+        depth: 400.0,
+        sim_steps: 4,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn balls_stay_inside_the_box() {
+    let mut world = World3D::new(&test_config());
+
+    for _ in 0..300 {
+        world.step(DT);
+    }
+
+    assert_eq!(world.bodies().len(), 300);
+    for ball in world.bodies() {
+        let size = glam::vec3(world.width, world.height, world.depth);
+        // The last boundary pass runs before the final move, so allow one step's travel
+        let slack = ball.velocity.abs() * DT + 1e-3;
+        assert!(ball
+            .position
+            .cmpge(glam::Vec3::splat(ball.radius) - slack)
+            .all());
+        assert!(ball.position.cmple(size - ball.radius + slack).all());
+    }
+}
+
+#[test]
+fn same_seed_gives_the_same_run() {
+    let config = test_config();
+    let mut first = World3D::new(&config);
+    let mut second = World3D::new(&config);
+
+    for _ in 0..120 {
+        first.step(DT);
+        second.step(DT);
+    }
+
+    for (ball, other_ball) in first.bodies().iter().zip(second.bodies()) {
+        assert_eq!(ball.position, other_ball.position);
+        assert_eq!(ball.velocity, other_ball.velocity);
+    }
+}
+
+#[test]
+fn gravity_pulls_the_balls_down() {
+    let mut world = World3D::new(&test_config());
+    let mean_height = |world: &World3D| {
+        world
+            .bodies()
+            .iter()
+            .map(|ball| ball.position.y)
+            .sum::<f32>()
+            / world.bodies().len() as f32
+    };
+    let before = mean_height(&world);
+
+    for _ in 0..120 {
+        world.step(DT);
+    }
+
+    // y points down
+    assert!(mean_height(&world) > before + 50.0);
+}
+
+#[test]
+fn simulated_time_advances_by_each_step() {
+    let mut world = World3D::new(&test_config());
+
+    for _ in 0..4 {
+        world.step(0.25);
+    }
+    assert_eq!(world.time(), 1.0);
+
+    world.reset();
+    assert_eq!(world.time(), 0.0);
+}
+
+#[test]
+fn coincident_balls_stay_finite() {
+    let mut world = World3D::new(&Config {
+        ball_count_3d: 5,
+        ..test_config()
+    });
+    for ball in world.balls.iter_mut() {
+        ball.position = glam::vec3(200.0, 200.0, 200.0);
+    }
+
+    for _ in 0..60 {
+        world.step(DT);
+    }
+
+    for ball in world.bodies() {
+        assert!(ball.position.is_finite(), "{:?}", ball.position);
+        assert!(ball.velocity.is_finite(), "{:?}", ball.velocity);
+    }
+}